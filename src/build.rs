@@ -1,181 +1,530 @@
-use cargo_metadata::MetadataCommand;
-use std::path::PathBuf;
-use std::process::Command;
+use cargo_metadata::{MetadataCommand, Package};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-use crate::model::{Feature, Program};
+use crate::model::{
+    Artifact, BuildEvent, BuildJob, Feature, FeatureGroup, Framework, JobOutcome, OutputStream,
+    Program,
+};
+use crate::plan::{BuildPlan, PlannedBuild};
 
-pub type BuildSender = Sender<String>;
+pub type BuildSender = Sender<BuildEvent>;
 
-pub fn scan_programs() -> Vec<Program> {
-    let metadata = MetadataCommand::new()
-        .exec()
-        .expect("Failed to run cargo metadata");
+/// Shared flag used to ask a running build thread to stop. Cloning the token
+/// shares the flag, so the UI keeps one copy and the build thread another.
+/// The token also tracks the build command currently running, so it can be
+/// killed without waiting for the build thread to notice the flag.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    child: Arc<Mutex<Option<u32>>>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Cancels the build and kills the running command and everything it
+    /// spawned before returning. Used when sbt itself is about to exit.
+    pub fn cancel_now(&self) {
+        self.cancel();
+        let child = self.child.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pid) = *child {
+            kill_process_tree(pid);
+        }
+    }
+}
+
+/// Why `scan_programs` could not list the workspace's programs.
+#[derive(Debug)]
+pub enum ScanError {
+    /// `cargo metadata` ran but failed, usually because of a missing or
+    /// broken `Cargo.toml`. Carries cargo's stderr.
+    Cargo { stderr: String },
+    /// `cargo metadata` could not be run or its output could not be read.
+    Other(String),
+}
 
-    let current_dir = std::env::current_dir().expect("Failed to get current directory");
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::Cargo { .. } => write!(f, "cargo metadata failed"),
+            ScanError::Other(e) => write!(f, "failed to run cargo metadata: {}", e),
+        }
+    }
+}
 
-    metadata
+impl From<cargo_metadata::Error> for ScanError {
+    fn from(e: cargo_metadata::Error) -> Self {
+        match e {
+            cargo_metadata::Error::CargoMetadata { stderr } => ScanError::Cargo { stderr },
+            e => ScanError::Other(e.to_string()),
+        }
+    }
+}
+
+/// Crates providing a program entrypoint for non-Anchor programs.
+const NATIVE_ENTRYPOINT_CRATES: &[&str] = &[
+    "solana-program",
+    "solana-program-entrypoint",
+    "solana-nostd-entrypoint",
+];
+
+/// Decides whether `package` is a Solana program and which framework it uses.
+/// Anchor programs are recognised by their `anchor-lang` dependency; other
+/// programs must be `cdylib`s depending on an entrypoint crate.
+fn detect_framework(package: &Package) -> Option<Framework> {
+    let depends_on = |name: &str| package.dependencies.iter().any(|dep| dep.name == name);
+    if depends_on("anchor-lang") {
+        return Some(Framework::Anchor);
+    }
+
+    let is_cdylib = package
+        .targets
+        .iter()
+        .any(|t| t.crate_types.iter().any(|k| k.to_string() == "cdylib"));
+    if !is_cdylib {
+        return None;
+    }
+    if depends_on("pinocchio") {
+        Some(Framework::Pinocchio)
+    } else if NATIVE_ENTRYPOINT_CRATES.iter().any(|name| depends_on(name)) {
+        Some(Framework::Native)
+    } else {
+        None
+    }
+}
+
+/// sbt's section of a program's `[package.metadata]`:
+///
+/// ```toml
+/// [package.metadata.sbt.feature-groups]
+/// cluster = ["localnet", "devnet", "mainnet"]
+///
+/// [package.metadata.sbt.feature-descriptions]
+/// test-clock = "Lets tests warp the on-chain clock"
+/// ```
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct PackageMetadata {
+    /// Named groups of mutually exclusive features.
+    feature_groups: BTreeMap<String, Vec<String>>,
+    /// Help text for features, taking precedence over `[features]` comments.
+    feature_descriptions: BTreeMap<String, String>,
+}
+
+impl PackageMetadata {
+    /// Reads sbt's metadata from `package`. A malformed section is ignored
+    /// rather than hiding the program.
+    fn of(package: &Package) -> Self {
+        package
+            .metadata
+            .get("sbt")
+            .and_then(|sbt| serde_json::from_value(sbt.clone()).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Reads the comment lines directly above each entry of the `[features]`
+/// table in the manifest at `manifest_path`, keyed by feature name.
+fn feature_comments(manifest_path: &Path) -> BTreeMap<String, String> {
+    std::fs::read_to_string(manifest_path)
+        .map(|text| parse_feature_comments(&text))
+        .unwrap_or_default()
+}
+
+fn parse_feature_comments(text: &str) -> BTreeMap<String, String> {
+    let mut comments = BTreeMap::new();
+    let Ok(document) = text.parse::<toml_edit::DocumentMut>() else {
+        return comments;
+    };
+    let Some(features) = document.get("features").and_then(|f| f.as_table_like()) else {
+        return comments;
+    };
+    for (name, _) in features.iter() {
+        let Some((key, _)) = features.get_key_value(name) else {
+            continue;
+        };
+        let prefix = key
+            .leaf_decor()
+            .prefix()
+            .and_then(|p| p.as_str())
+            .unwrap_or_default()
+            // The prefix ends with the key's own indentation.
+            .trim_end_matches([' ', '\t']);
+        // Only the block touching the entry counts; a blank line ends it.
+        let mut lines = prefix
+            .lines()
+            .rev()
+            .map(str::trim)
+            .take_while(|line| line.starts_with('#'))
+            .map(|line| line.trim_start_matches('#').trim())
+            .collect::<Vec<_>>();
+        lines.reverse();
+        let comment = lines.join("\n").trim().to_string();
+        if !comment.is_empty() {
+            comments.insert(name.to_string(), comment);
+        }
+    }
+    comments
+}
+
+/// Result of scanning a directory for programs.
+pub struct Workspace {
+    /// Root of the enclosing cargo workspace, where `.sbt/` lives.
+    pub root: PathBuf,
+    pub programs: Vec<Program>,
+}
+
+/// Lists the Solana programs of the cargo workspace containing `root`,
+/// keeping only those located under `root`.
+pub fn scan_programs(root: &Path) -> Result<Workspace, ScanError> {
+    let metadata = MetadataCommand::new().current_dir(root).exec()?;
+
+    let programs = metadata
         .packages
         .iter()
-        .filter(|p| {
-            let is_anchor_program = p.dependencies.iter().any(|dep| dep.name == "anchor-lang");
-            let manifest_path = PathBuf::from(&p.manifest_path);
-            let is_in_current_dir = manifest_path.starts_with(current_dir.clone());
-            is_anchor_program && is_in_current_dir
-        })
-        .map(|p| {
+        .filter(|p| PathBuf::from(&p.manifest_path).starts_with(root))
+        .filter_map(|p| Some((p, detect_framework(p)?)))
+        .map(|(p, framework)| {
             let manifest_path = PathBuf::from(&p.manifest_path);
             let program_path = manifest_path
                 .parent()
                 .expect("Failed to get parent directory")
                 .to_path_buf();
 
+            let sbt_metadata = PackageMetadata::of(p);
+            let mut descriptions = feature_comments(&manifest_path);
+            descriptions.extend(sbt_metadata.feature_descriptions);
             let features = p
                 .features
                 .iter()
                 .map(|(name, deps)| Feature {
                     name: name.clone(),
                     sub_features: deps.clone(),
+                    description: descriptions.get(name).cloned(),
                 })
                 .collect();
 
+            let lib_name = p
+                .targets
+                .iter()
+                .find(|t| t.crate_types.iter().any(|k| k.to_string() == "cdylib"))
+                .map(|t| t.name.replace('-', "_"))
+                .unwrap_or_else(|| p.name.replace('-', "_"));
+
+            let feature_groups = sbt_metadata
+                .feature_groups
+                .into_iter()
+                .map(|(name, features)| FeatureGroup { name, features })
+                .collect();
+
             Program {
                 name: p.name.clone(),
                 features,
                 selected: Vec::new(),
+                included: false,
                 path: program_path,
+                framework,
+                lib_name,
+                target_dir: metadata.target_directory.clone().into_std_path_buf(),
+                no_default_features: false,
+                feature_groups,
             }
         })
-        .collect()
-}
-
-pub fn run_build(programs: Vec<Program>, tx: BuildSender, build_dir: Option<String>) {
-    for program in programs {
-        let selected_features: Vec<String> = program
-            .features
-            .iter()
-            .zip(&program.selected)
-            .filter(|(_, &sel)| sel)
-            .map(|(f, _)| f.name.clone())
-            .collect();
-
-        if !selected_features.is_empty() {
-            let feature_args = selected_features.join(",");
-            let cmd = match &build_dir {
-                Some(dir) => format!(
-                    "anchor build -p {} -t {} -- --features {}",
-                    program.name, dir, feature_args
-                ),
-                None => format!(
-                    "anchor build -p {} -- --features {}",
-                    program.name, feature_args
-                ),
-            };
-
-            tx.send(format!(
-                "Running: {} (from {})",
-                cmd,
-                program.path.display()
-            ))
+        .collect();
+
+    Ok(Workspace {
+        root: metadata.workspace_root.into_std_path_buf(),
+        programs,
+    })
+}
+
+/// Runs `job` on a background thread, returning the token that cancels it.
+pub fn spawn_job(job: BuildJob, tx: BuildSender) -> CancelToken {
+    let cancel = CancelToken::default();
+    let job_cancel = cancel.clone();
+    let plan = BuildPlan::new(&job);
+    thread::spawn(move || run_plan(plan, tx, job_cancel));
+    cancel
+}
+
+/// Runs each step of `plan` in order, skipping the rest once cancelled.
+fn run_plan(plan: BuildPlan, tx: BuildSender, cancel: CancelToken) {
+    for step in &plan.steps {
+        if cancel.is_cancelled() {
+            tx.send(BuildEvent::JobSkipped {
+                program: step.program.name.clone(),
+            })
             .unwrap();
+            continue;
+        }
+        run_program_build(step, &tx, &cancel);
+    }
+    send_summary(&tx, &cancel);
+}
 
-            let mut command = Command::new("anchor");
-            command
-                .args(["build", "-p", &program.name])
-                .current_dir(&program.path)
-                .envs(std::env::vars());
-            if let Some(dir) = &build_dir {
-                command.args(["-t", dir]);
-            }
-            command.args(["--", "--features", &feature_args]);
+fn run_program_build(step: &PlannedBuild, tx: &BuildSender, cancel: &CancelToken) {
+    tx.send(BuildEvent::JobStarted {
+        program: step.program.name.clone(),
+        features: step.features.clone(),
+        no_default_features: step.program.no_default_features,
+        command: step.command_line(),
+        cwd: step.cwd.clone(),
+    })
+    .unwrap();
 
-            let output = command.output();
+    let started = Instant::now();
+    let result = stream_command(step.command(), tx, cancel);
+    let (outcome, exit_code) = match result {
+        Ok(status) if cancel.is_cancelled() => (JobOutcome::Cancelled, status.code()),
+        Ok(status) if status.success() => (JobOutcome::Succeeded, status.code()),
+        Ok(status) => (JobOutcome::Failed, status.code()),
+        Err(e) => (JobOutcome::Error(e.to_string()), None),
+    };
 
-            match output {
-                Ok(output) => {
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    if !stdout.is_empty() {
-                        tx.send(stdout.to_string()).unwrap();
-                    }
-                    if !stderr.is_empty() {
-                        tx.send(stderr.to_string()).unwrap();
-                    }
-                    if !output.status.success() {
-                        tx.send(format!("Build failed with code {:?}", output.status.code()))
-                            .unwrap();
-                    } else {
-                        tx.send("Build succeeded.".to_string()).unwrap();
+    let duration = started.elapsed();
+    let (artifacts, idl) = if outcome == JobOutcome::Succeeded {
+        collect_artifacts(&step.program, step.build_dir.as_deref())
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    tx.send(BuildEvent::JobFinished {
+        program: step.program.name.clone(),
+        outcome,
+        exit_code,
+        duration,
+        artifacts,
+        idl,
+    })
+    .unwrap();
+}
+
+/// Finds the files a successful build left behind: the `.so` and keypair in
+/// `target/deploy`, and the IDL JSON and TypeScript types.
+fn collect_artifacts(program: &Program, build_dir: Option<&str>) -> (Vec<Artifact>, Vec<PathBuf>) {
+    let deploy_dir = program.target_dir.join("deploy");
+    let artifacts = [
+        deploy_dir.join(format!("{}.so", program.lib_name)),
+        deploy_dir.join(format!("{}-keypair.json", program.lib_name)),
+    ]
+    .into_iter()
+    .filter_map(|path| {
+        let size = std::fs::metadata(&path).ok()?.len();
+        Some(Artifact { path, size })
+    })
+    .collect();
+
+    let mut idl = vec![
+        program
+            .target_dir
+            .join("idl")
+            .join(format!("{}.json", program.lib_name)),
+        program
+            .target_dir
+            .join("types")
+            .join(format!("{}.ts", program.lib_name)),
+    ];
+    if let Some(dir) = build_dir {
+        idl.push(PathBuf::from(dir).join(format!("{}.ts", program.lib_name)));
+    }
+    idl.retain(|path| path.is_file());
+
+    (artifacts, idl)
+}
+
+fn send_summary(tx: &BuildSender, cancel: &CancelToken) {
+    if cancel.is_cancelled() {
+        tx.send(BuildEvent::Cancelled).unwrap();
+    } else {
+        tx.send(BuildEvent::AllDone).unwrap();
+    }
+}
+
+/// Spawns `command` with piped stdout/stderr and forwards each line to `tx` as
+/// soon as it is read, so output from both streams arrives interleaved in the
+/// order the child produced it. If `cancel` fires while the child is running,
+/// the child and everything it spawned are killed.
+fn stream_command(
+    mut command: Command,
+    tx: &BuildSender,
+    cancel: &CancelToken,
+) -> io::Result<ExitStatus> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // Put the child in its own process group so cancelling also reaches
+        // the cargo and rustc processes anchor spawns. Being out of the
+        // terminal's foreground group, it would be stopped by SIGTTIN if it
+        // read the terminal, hence the null stdin.
+        command.process_group(0);
+    }
+    // Hold the lock while spawning and reaping so `cancel_now` never signals
+    // a pid that has already been reused.
+    let mut child = {
+        let mut running = cancel.child.lock().unwrap_or_else(|e| e.into_inner());
+        let child = command.spawn()?;
+        *running = Some(child.id());
+        child
+    };
+
+    let readers = [
+        child
+            .stdout
+            .take()
+            .map(|out| forward_lines(out, OutputStream::Stdout, tx.clone())),
+        child
+            .stderr
+            .take()
+            .map(|err| forward_lines(err, OutputStream::Stderr, tx.clone())),
+    ];
+
+    let status = loop {
+        let mut running = cancel.child.lock().unwrap_or_else(|e| e.into_inner());
+        let status = match child.try_wait() {
+            Ok(Some(status)) => Ok(status),
+            Ok(None) if cancel.is_cancelled() => {
+                kill_process_tree(child.id());
+                let _ = child.kill();
+                child.wait()
+            }
+            Ok(None) => {
+                drop(running);
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            Err(e) => Err(e),
+        };
+        *running = None;
+        break status;
+    };
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+    status
+}
+
+fn forward_lines<R: Read + Send + 'static>(
+    reader: R,
+    stream: OutputStream,
+    tx: BuildSender,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&buf);
+                    let line = line.trim_end_matches(['\r', '\n']).to_string();
+                    let event = BuildEvent::Output { stream, line };
+                    if tx.send(event).is_err() {
+                        break;
                     }
                 }
-                Err(e) => {
-                    tx.send(format!("Command failed: {}", e)).unwrap();
-                }
             }
         }
+    })
+}
+
+#[cfg(unix)]
+fn kill_process_tree(pid: u32) {
+    // The child leads its own process group (see `stream_command`), so a
+    // negative pid signals every process in it.
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
     }
-    tx.send("Build complete.".to_string()).unwrap();
 }
 
-pub fn build_all(
-    programs: Vec<Program>,
-    tx: BuildSender,
-    use_prod: bool,
-    build_dir: Option<String>,
-) {
-    for program in programs {
-        let cmd = match (use_prod, &build_dir) {
-            (true, Some(dir)) => format!(
-                "anchor build -p {} -t {} -- --features prod",
-                program.name, dir
-            ),
-            (true, None) => format!("anchor build -p {} -- --features prod", program.name),
-            (false, Some(dir)) => format!("anchor build -p {} -t {}", program.name, dir),
-            (false, None) => format!("anchor build -p {}", program.name),
-        };
+#[cfg(windows)]
+fn kill_process_tree(pid: u32) {
+    let _ = Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .status();
+}
 
-        tx.send(format!(
-            "Running: {} (from {})",
-            cmd,
-            program.path.display()
-        ))
-        .unwrap();
-
-        let mut command = Command::new("anchor");
-        command
-            .args(["build", "-p", &program.name])
-            .current_dir(&program.path)
-            .envs(std::env::vars());
-        if let Some(dir) = &build_dir {
-            command.args(["-t", dir]);
-        }
-        if use_prod {
-            command.args(["--", "--features", "prod"]);
-        }
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
-        let output = command.output();
+/// Builds run in their own process group on unix, so Ctrl-C no longer reaches
+/// them directly. Record it instead, for the caller to cancel the build with.
+#[cfg(unix)]
+pub fn install_interrupt_handler() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
 
-        match output {
-            Ok(output) => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                if !stdout.is_empty() {
-                    tx.send(stdout.to_string()).unwrap();
-                }
-                if !stderr.is_empty() {
-                    tx.send(stderr.to_string()).unwrap();
-                }
-                if !output.status.success() {
-                    tx.send(format!("Build failed with code {:?}", output.status.code()))
-                        .unwrap();
-                } else {
-                    tx.send("Build succeeded.".to_string()).unwrap();
-                }
-            }
-            Err(e) => {
-                tx.send(format!("Command failed: {}", e)).unwrap();
-            }
-        }
+#[cfg(not(unix))]
+pub fn install_interrupt_handler() {}
+
+/// Whether Ctrl-C was pressed since `install_interrupt_handler` was called.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feature_comments_take_the_block_above_each_entry() {
+        let comments = parse_feature_comments(
+            r#"
+[package]
+name = "foo"
+
+[features]
+# Builds for localnet.
+# Uses the test clock.
+local = []
+
+# Not attached to prod.
+
+prod = []
+devnet = [] # trailing comments don't count
+"#,
+        );
+        assert_eq!(
+            comments.get("local").map(String::as_str),
+            Some("Builds for localnet.\nUses the test clock.")
+        );
+        assert_eq!(comments.get("prod"), None);
+        assert_eq!(comments.get("devnet"), None);
+    }
+
+    #[test]
+    fn feature_comments_in_an_indented_table() {
+        let comments = parse_feature_comments("[features]\n  # Skips auth.\n  skip-auth = []\n");
+        assert_eq!(
+            comments.get("skip-auth").map(String::as_str),
+            Some("Skips auth.")
+        );
     }
-    tx.send("Build complete.".to_string()).unwrap();
 }
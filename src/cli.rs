@@ -0,0 +1,365 @@
+use clap::{Parser, Subcommand};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::Duration;
+
+use crate::build::{install_interrupt_handler, interrupted, scan_programs, spawn_job, ScanError};
+use crate::config::{BuildProfile, WorkspaceConfig};
+use crate::model::{BuildEvent, BuildJob, JobOutcome, OutputStream, Program};
+use crate::plan::BuildPlan;
+use crate::presets::{load_presets, preset_job, validate_preset};
+use crate::report::ReportBuilder;
+
+/// Build and manage Solana programs. Run without a subcommand to open the GUI.
+#[derive(Parser)]
+#[command(name = "sbt", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+pub enum CliCommand {
+    /// List detected programs and their features
+    List,
+    /// Build a single program, or every program in a saved preset
+    Build {
+        /// Program to build
+        #[arg(long, required_unless_present = "preset", conflicts_with = "preset")]
+        program: Option<String>,
+        /// Comma-separated features to enable for --program
+        #[arg(long, value_delimiter = ',', conflicts_with = "preset")]
+        features: Vec<String>,
+        /// Build --program without its default features
+        #[arg(long, conflicts_with = "preset")]
+        no_default_features: bool,
+        /// Name of a saved preset to build
+        #[arg(long)]
+        preset: Option<String>,
+        /// Build --preset even if it names programs or features the
+        /// workspace no longer has, skipping those entries
+        #[arg(long, conflicts_with = "program")]
+        allow_stale: bool,
+        /// TypeScript IDL output directory (passed to anchor as -t)
+        #[arg(long)]
+        idl_dir: Option<String>,
+        /// Write a JSON build report to this path
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// Print the commands the build would run instead of running them
+        #[arg(long, conflicts_with = "report")]
+        dry_run: bool,
+    },
+    /// Build every program in the workspace
+    BuildAll {
+        /// Build profile from .sbt/config.toml; without one, programs are
+        /// built with their default features
+        #[arg(long)]
+        profile: Option<String>,
+        /// TypeScript IDL output directory (passed to anchor as -t)
+        #[arg(long)]
+        idl_dir: Option<String>,
+        /// Write a JSON build report to this path
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// Print the commands the build would run instead of running them
+        #[arg(long, conflicts_with = "report")]
+        dry_run: bool,
+    },
+}
+
+pub fn run(command: CliCommand) -> ExitCode {
+    let root = std::env::current_dir().unwrap_or_default();
+    let (config_root, programs) = match scan_programs(&root) {
+        Ok(workspace) => (workspace.root, workspace.programs),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            if let ScanError::Cargo { stderr } = &e {
+                eprintln!("{}", stderr.trim_end());
+            }
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match command {
+        CliCommand::List => list(programs),
+        CliCommand::Build {
+            program,
+            features,
+            no_default_features,
+            preset,
+            allow_stale,
+            idl_dir,
+            report,
+            dry_run,
+        } => {
+            let job = match (preset, program) {
+                (Some(preset), _) => {
+                    preset_build_job(&config_root, &root, programs, preset, allow_stale, idl_dir)
+                }
+                (None, Some(program)) => {
+                    program_job(programs, program, features, no_default_features, idl_dir)
+                }
+                (None, None) => unreachable!("clap requires --program without --preset"),
+            };
+            match job {
+                Ok(job) if dry_run => print_plan(&job),
+                Ok(job) => run_job(job, report.as_deref()),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        CliCommand::BuildAll {
+            profile,
+            idl_dir,
+            report,
+            dry_run,
+        } => match profile_job(&config_root, programs, profile, idl_dir) {
+            Ok(job) if dry_run => print_plan(&job),
+            Ok(job) => run_job(job, report.as_deref()),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+fn list(programs: Vec<Program>) -> ExitCode {
+    if programs.is_empty() {
+        println!("No programs found.");
+    }
+    for program in programs {
+        println!(
+            "{} [{}] ({})",
+            program.name,
+            program.framework.label(),
+            program.path.display()
+        );
+        for feature in program.features {
+            let mut line = if feature.sub_features.is_empty() {
+                format!("    {}", feature.name)
+            } else {
+                format!(
+                    "    {} = [{}]",
+                    feature.name,
+                    feature.sub_features.join(", ")
+                )
+            };
+            if let Some(description) = &feature.description {
+                line.push_str(&format!("  # {}", description.replace('\n', " ")));
+            }
+            println!("{}", line);
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn preset_build_job(
+    config_root: &Path,
+    root: &Path,
+    programs: Vec<Program>,
+    name: String,
+    allow_stale: bool,
+    idl_dir: Option<String>,
+) -> Result<BuildJob, String> {
+    let presets = load_presets(config_root, root).map_err(|e| e.to_string())?;
+    // A personal preset overrides a shared one of the same name.
+    let preset = presets
+        .iter()
+        .rev()
+        .find(|p| p.name == name)
+        .ok_or_else(|| format!("no preset named '{}'", name))?;
+    // A stale preset would build something other than its name promises, so
+    // it only builds when asked to explicitly.
+    let issues = validate_preset(preset, &programs).describe();
+    if !issues.is_empty() && !allow_stale {
+        for issue in &issues {
+            eprintln!("error: preset '{}': {}", preset.name, issue);
+        }
+        return Err(format!(
+            "preset '{}' is out of date; fix it or pass --allow-stale to skip the missing entries",
+            preset.name
+        ));
+    }
+    for issue in &issues {
+        eprintln!("warning: preset '{}': {}; skipping it", preset.name, issue);
+    }
+    let buildable = preset
+        .programs
+        .iter()
+        .any(|entry| programs.iter().any(|p| p.name == entry.name));
+    if !buildable {
+        return Err(format!(
+            "preset '{}' has no programs left to build",
+            preset.name
+        ));
+    }
+    // An explicit --idl-dir wins over the preset's own directory.
+    let mut preset = preset.clone();
+    if idl_dir.is_some() {
+        preset.build_dir = idl_dir;
+    }
+    let config = WorkspaceConfig::load(config_root)?;
+    preset_job(&programs, &preset, &config, None)
+}
+
+fn program_job(
+    programs: Vec<Program>,
+    name: String,
+    features: Vec<String>,
+    no_default_features: bool,
+    idl_dir: Option<String>,
+) -> Result<BuildJob, String> {
+    let mut program = programs
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| format!("no program named '{}'", name))?;
+    program.selected = vec![false; program.features.len()];
+    program.included = true;
+    program.no_default_features = no_default_features;
+    for feature in &features {
+        let idx = program
+            .features
+            .iter()
+            .position(|f| f.name == *feature)
+            .ok_or_else(|| format!("{} has no feature '{}'", program.name, feature))?;
+        program.selected[idx] = true;
+    }
+
+    Ok(BuildJob::Selected {
+        programs: vec![program],
+        build_dir: idl_dir,
+    })
+}
+
+fn profile_job(
+    config_root: &Path,
+    programs: Vec<Program>,
+    profile: Option<String>,
+    idl_dir: Option<String>,
+) -> Result<BuildJob, String> {
+    let profile = match profile {
+        Some(name) => {
+            let config = WorkspaceConfig::load(config_root)?;
+            let profile = config.profile(&name).cloned().ok_or_else(|| {
+                let names = config
+                    .profiles
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>();
+                format!(
+                    "no profile named '{}' (available: {})",
+                    name,
+                    names.join(", ")
+                )
+            })?;
+            for program in &programs {
+                let missing = profile.missing_features(program);
+                if !missing.is_empty() {
+                    eprintln!(
+                        "warning: {} has no feature {}; building without it",
+                        program.name,
+                        missing.join(", ")
+                    );
+                }
+            }
+            profile
+        }
+        None => BuildProfile::empty("Default"),
+    };
+    Ok(BuildJob::All {
+        programs,
+        profile,
+        build_dir: idl_dir,
+    })
+}
+
+/// Prints the commands `job` would run as a shell script, without running them.
+fn print_plan(job: &BuildJob) -> ExitCode {
+    if !report_violations(job) {
+        return ExitCode::FAILURE;
+    }
+    let plan = BuildPlan::new(job);
+    if !plan.steps.is_empty() {
+        println!("{}", plan.shell_script());
+    }
+    ExitCode::SUCCESS
+}
+
+/// Prints `job`'s group violations as errors, returning whether it may run.
+fn report_violations(job: &BuildJob) -> bool {
+    let violations = job.group_violations();
+    for violation in &violations {
+        eprintln!("error: {}", violation);
+    }
+    violations.is_empty()
+}
+
+/// Runs `job` to completion, echoing its output, and fails if any program failed.
+fn run_job(job: BuildJob, report_path: Option<&Path>) -> ExitCode {
+    if !report_violations(&job) {
+        return ExitCode::FAILURE;
+    }
+    install_interrupt_handler();
+    let (tx, rx) = channel();
+    let cancel = spawn_job(job, tx);
+
+    let mut report_builder = ReportBuilder::default();
+    let mut failed = false;
+    loop {
+        if interrupted() {
+            cancel.cancel();
+        }
+        let event = match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        match &event {
+            BuildEvent::Output {
+                stream: OutputStream::Stdout,
+                line,
+                ..
+            } => println!("{}", line),
+            BuildEvent::Output {
+                stream: OutputStream::Stderr,
+                line,
+                ..
+            } => eprintln!("{}", line),
+            BuildEvent::JobFinished { outcome, .. } => {
+                failed |= *outcome != JobOutcome::Succeeded;
+                eprintln!("{}", event.log_line());
+            }
+            BuildEvent::Cancelled => {
+                failed = true;
+                eprintln!("{}", event.log_line());
+            }
+            _ => eprintln!("{}", event.log_line()),
+        }
+        let _ = std::io::stdout().flush();
+
+        if let Some(report) = report_builder.record(&event) {
+            if let Some(path) = report_path {
+                if let Err(e) = report.write(path) {
+                    eprintln!("error: failed to write report to {}: {}", path.display(), e);
+                    failed = true;
+                }
+            }
+        }
+        if event.is_terminal() {
+            break;
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::model::Program;
+
+pub const CONFIG_DIR: &str = ".sbt";
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// A named "build everything" configuration, rendered as one button per profile.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuildProfile {
+    pub name: String,
+    /// Enabled on every program that defines them; programs lacking a feature
+    /// are built without it.
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Extra arguments for `anchor build`, before `--`.
+    #[serde(default)]
+    pub anchor_args: Vec<String>,
+    /// Extra arguments passed through to cargo.
+    #[serde(default)]
+    pub cargo_args: Vec<String>,
+    /// Environment variables set for the build, on top of sbt's own.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl BuildProfile {
+    /// A profile that builds every program with its default features.
+    pub fn empty(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            features: Vec::new(),
+            anchor_args: Vec::new(),
+            cargo_args: Vec::new(),
+            env: BTreeMap::new(),
+        }
+    }
+
+    /// The profile's features that `program` defines.
+    pub fn features_for(&self, program: &Program) -> Vec<String> {
+        self.features
+            .iter()
+            .filter(|name| program.features.iter().any(|f| f.name == **name))
+            .cloned()
+            .collect()
+    }
+
+    /// The profile's features that `program` does not define.
+    pub fn missing_features(&self, program: &Program) -> Vec<String> {
+        self.features
+            .iter()
+            .filter(|name| !program.features.iter().any(|f| f.name == **name))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Workspace-level settings read from `.sbt/config.toml`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkspaceConfig {
+    #[serde(default = "default_profiles")]
+    pub profiles: Vec<BuildProfile>,
+}
+
+impl Default for WorkspaceConfig {
+    fn default() -> Self {
+        Self {
+            profiles: default_profiles(),
+        }
+    }
+}
+
+/// Profiles used when the workspace doesn't define any.
+fn default_profiles() -> Vec<BuildProfile> {
+    vec![
+        BuildProfile {
+            features: vec!["prod".to_string()],
+            ..BuildProfile::empty("Prod")
+        },
+        BuildProfile::empty("Default"),
+    ]
+}
+
+impl WorkspaceConfig {
+    pub fn path(root: &Path) -> PathBuf {
+        root.join(CONFIG_DIR).join(CONFIG_FILE)
+    }
+
+    /// Loads the config for the workspace at `root`. A missing file yields the
+    /// defaults; a file that can't be read or parsed is an error.
+    pub fn load(root: &Path) -> Result<Self, String> {
+        let path = Self::path(root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&data).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&BuildProfile> {
+        self.profiles.iter().find(|p| p.name == name).or_else(|| {
+            self.profiles
+                .iter()
+                .find(|p| p.name.eq_ignore_ascii_case(name))
+        })
+    }
+}
@@ -1,37 +1,87 @@
+use clap::Parser;
 use eframe::egui;
-use std::fs;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
 use std::sync::mpsc::channel;
 
 mod build;
+mod cli;
+mod config;
 mod model;
+mod plan;
+mod presets;
+mod report;
+mod settings;
 mod ui;
 
-use build::scan_programs;
-use model::BuildTool;
+use cli::Cli;
+use config::WorkspaceConfig;
+use model::{BuildEvent, BuildState, BuildTool, ProgramBuildStatus};
+use report::ReportBuilder;
+use settings::Settings;
 use ui::render_ui;
 
-fn main() -> Result<(), eframe::Error> {
-    let programs = scan_programs();
-    let (tx, rx) = channel();
+fn main() -> ExitCode {
+    match Cli::parse().command {
+        Some(command) => cli::run(command),
+        None => match run_gui() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
 
-    // Load presets from file, or use empty vec if file doesn’t exist
-    let presets = fs::read_to_string("presets.json")
-        .ok()
-        .and_then(|data| serde_json::from_str(&data).ok())
-        .unwrap_or_else(Vec::new);
+fn run_gui() -> Result<(), eframe::Error> {
+    let (tx, rx) = channel();
 
-    let app = BuildTool {
-        programs,
+    let mut app = BuildTool {
+        workspace_root: PathBuf::new(),
+        config_root: PathBuf::new(),
+        scan_error: None,
+        programs: Vec::new(),
         selected_program: None,
         build_output: String::new(),
         build_rx: rx,
         build_tx: tx,
         build_dir: None,
-        presets,
+        build_state: BuildState::default(),
+        build_status: HashMap::new(),
+        report_builder: ReportBuilder::default(),
+        last_report: None,
+        presets: Vec::new(),
+        preset_error: None,
+        settings: Settings::load(),
+        config: WorkspaceConfig::default(),
+        config_error: None,
+        pending_profile_build: None,
+        blocked_build: None,
+        plan_preview: None,
+        preset_editor: None,
+        preset_issues: Vec::new(),
+        preset_repair: None,
     };
 
+    // Prefer the workspace sbt was launched from; fall back to the last one
+    // used when started somewhere without programs.
+    let last_workspace = app.settings.last_workspace().map(PathBuf::from);
+    app.open_workspace(std::env::current_dir().unwrap_or_default());
+    if app.scan_error.is_some() || app.programs.is_empty() {
+        if let Some(last) = last_workspace.filter(|last| *last != app.workspace_root) {
+            app.open_workspace(last);
+        }
+    }
+
+    // Builds don't receive the terminal's Ctrl-C themselves (see
+    // `stream_command`), so close the window instead and kill them in `on_exit`.
+    build::install_interrupt_handler();
+
+    let [width, height] = app.settings.window_size.unwrap_or([800.0, 700.0]);
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size(egui::Vec2::new(800.0, 700.0)),
+        viewport: egui::ViewportBuilder::default().with_inner_size(egui::Vec2::new(width, height)),
         ..Default::default()
     };
     eframe::run_native(
@@ -43,11 +93,57 @@ fn main() -> Result<(), eframe::Error> {
 
 impl eframe::App for BuildTool {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        while let Ok(output) = self.build_rx.try_recv() {
-            self.build_output.push_str(&output);
+        if build::interrupted() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+        while let Ok(event) = self.build_rx.try_recv() {
+            self.build_output.push_str(&event.log_line());
             self.build_output.push('\n');
+            if let Some(report) = self.report_builder.record(&event) {
+                self.last_report = Some(report);
+            }
+            match &event {
+                BuildEvent::JobStarted { program, .. } => {
+                    self.build_status
+                        .insert(program.clone(), ProgramBuildStatus::running());
+                }
+                BuildEvent::JobFinished {
+                    program,
+                    outcome,
+                    duration,
+                    ..
+                } => {
+                    self.build_status
+                        .entry(program.clone())
+                        .or_insert_with(ProgramBuildStatus::running)
+                        .finish(outcome, *duration);
+                }
+                BuildEvent::JobSkipped { program } => {
+                    self.build_status
+                        .insert(program.clone(), ProgramBuildStatus::skipped());
+                }
+                _ => {}
+            }
+            if event.is_terminal() {
+                self.build_state.running = None;
+                if self.build_state.start_next(&self.build_tx) {
+                    self.build_output.push('\n');
+                }
+            }
         }
         render_ui(self, ctx, frame);
+        if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
+            self.settings.window_size = Some([rect.width(), rect.height()]);
+        }
         ctx.request_repaint();
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // The build runs in its own process group and would outlive sbt.
+        if let Some(running) = &self.build_state.running {
+            running.cancel.cancel_now();
+        }
+        self.store_workspace_settings();
+        self.settings.save();
+    }
 }
@@ -1,12 +1,144 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::path::PathBuf;
 use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
 
 use serde::{Deserialize, Serialize};
 
+use crate::build::{scan_programs, spawn_job, BuildSender, CancelToken, ScanError};
+use crate::config::{BuildProfile, WorkspaceConfig};
+use crate::plan::{shell_quote, shell_split};
+use crate::presets::{
+    load_presets, save_presets, validate_preset, PresetError, PresetIssues, PresetRepair,
+};
+use crate::report::{BuildReport, ReportBuilder};
+use crate::settings::{Settings, WorkspaceSettings};
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Feature {
     pub name: String,
     pub sub_features: Vec<String>,
+    /// From the comment above the feature in `[features]`, or
+    /// `[package.metadata.sbt.feature-descriptions]`.
+    pub description: Option<String>,
+}
+
+impl Feature {
+    /// What enabling this feature turns on.
+    pub fn implies(&self) -> impl Iterator<Item = FeatureRef> + '_ {
+        self.sub_features
+            .iter()
+            .map(|entry| FeatureRef::parse(entry))
+    }
+}
+
+/// One entry of a feature's list in `[features]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FeatureRef {
+    /// Another feature of the same package.
+    Local(String),
+    /// `dep:name`, an optional dependency.
+    Dependency(String),
+    /// `crate/feature`, or `crate?/feature` when `weak`, which only enables
+    /// the feature if the optional dependency is enabled some other way.
+    DependencyFeature {
+        krate: String,
+        feature: String,
+        weak: bool,
+    },
+}
+
+impl FeatureRef {
+    pub fn parse(entry: &str) -> Self {
+        if let Some(dep) = entry.strip_prefix("dep:") {
+            return FeatureRef::Dependency(dep.to_string());
+        }
+        match entry.split_once('/') {
+            Some((krate, feature)) => {
+                let (krate, weak) = match krate.strip_suffix('?') {
+                    Some(krate) => (krate, true),
+                    None => (krate, false),
+                };
+                FeatureRef::DependencyFeature {
+                    krate: krate.to_string(),
+                    feature: feature.to_string(),
+                    weak,
+                }
+            }
+            None => FeatureRef::Local(entry.to_string()),
+        }
+    }
+
+    /// How the entry is shown in the feature tree.
+    pub fn describe(&self) -> String {
+        match self {
+            FeatureRef::Local(name) => name.clone(),
+            FeatureRef::Dependency(dep) => format!("📦 {} (optional dependency)", dep),
+            FeatureRef::DependencyFeature {
+                krate,
+                feature,
+                weak: false,
+            } => format!("📦 {}/{}", krate, feature),
+            FeatureRef::DependencyFeature {
+                krate,
+                feature,
+                weak: true,
+            } => format!("📦 {}/{} (if {} is enabled)", krate, feature, krate),
+        }
+    }
+}
+
+/// Everything a set of features turns on, following features that enable
+/// other features.
+#[derive(Default)]
+pub struct ResolvedFeatures {
+    /// Local features reached through another feature, each with the feature
+    /// that enabled it.
+    pub implied: Vec<(String, String)>,
+    /// Optional dependencies and dependency features turned on.
+    pub dependencies: Vec<FeatureRef>,
+}
+
+impl ResolvedFeatures {
+    pub fn enabler_of(&self, feature: &str) -> Option<&str> {
+        self.implied
+            .iter()
+            .find(|(name, _)| name == feature)
+            .map(|(_, parent)| parent.as_str())
+    }
+
+    /// Whether `feature` is `ancestor` or was enabled, directly or not, by it.
+    fn enabled_through(&self, feature: &str, ancestor: &str) -> bool {
+        let mut current = Some(feature);
+        while let Some(name) = current {
+            if name == ancestor {
+                return true;
+            }
+            current = self.enabler_of(name);
+        }
+        false
+    }
+}
+
+/// How a program is built: Anchor programs with `anchor build`, the rest
+/// with `cargo build-sbf`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Framework {
+    Anchor,
+    Native,
+    Pinocchio,
+}
+
+impl Framework {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Framework::Anchor => "anchor",
+            Framework::Native => "native",
+            Framework::Pinocchio => "pinocchio",
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -14,21 +146,1132 @@ pub struct Program {
     pub name: String,
     pub features: Vec<Feature>,
     pub selected: Vec<bool>,
+    /// Whether "Build" builds this program, with its selected features or,
+    /// if none are selected, its default ones.
+    pub included: bool,
     pub path: PathBuf,
+    pub framework: Framework,
+    /// Crate name of the program's library target, which names its artifacts.
+    pub lib_name: String,
+    pub target_dir: PathBuf,
+    /// Pass `--no-default-features` when building.
+    pub no_default_features: bool,
+    /// Features of which at most one may be enabled at a time, from
+    /// `[package.metadata.sbt.feature-groups]`.
+    pub feature_groups: Vec<FeatureGroup>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
+pub struct FeatureGroup {
+    pub name: String,
+    pub features: Vec<String>,
+}
+
+impl Program {
+    /// Names of the checked features.
+    pub fn selected_features(&self) -> Vec<String> {
+        self.features
+            .iter()
+            .zip(&self.selected)
+            .filter(|(_, &sel)| sel)
+            .map(|(f, _)| f.name.clone())
+            .collect()
+    }
+
+    /// Checks or unchecks the feature `name`, if the program defines it.
+    /// Checking a feature unchecks the others in its exclusive groups and
+    /// includes the program in "Build".
+    pub fn set_feature(&mut self, name: &str, on: bool) {
+        let Some(idx) = self.features.iter().position(|f| f.name == name) else {
+            return;
+        };
+        self.selected.resize(self.features.len(), false);
+        if on {
+            for group in self
+                .feature_groups
+                .iter()
+                .filter(|g| g.features.iter().any(|f| f == name))
+            {
+                for other in &group.features {
+                    if let Some(other_idx) = self.features.iter().position(|f| f.name == *other) {
+                        self.selected[other_idx] = false;
+                    }
+                }
+            }
+            self.included = true;
+        }
+        self.selected[idx] = on;
+    }
+
+    /// Resolves what enabling `roots` turns on. Roots themselves are only
+    /// listed in `implied` when another root reaches them too.
+    pub fn resolve_features(&self, roots: &[String]) -> ResolvedFeatures {
+        let mut resolved = ResolvedFeatures::default();
+        let mut pending = roots.to_vec();
+        while let Some(name) = pending.pop() {
+            let Some(feature) = self.features.iter().find(|f| f.name == name) else {
+                continue;
+            };
+            for implied in feature.implies() {
+                match implied {
+                    FeatureRef::Local(sub) => {
+                        // In a cycle, don't credit a feature to one it enabled
+                        // itself; both would be locked on.
+                        let known = self.features.iter().any(|f| f.name == sub);
+                        if known
+                            && resolved.enabler_of(&sub).is_none()
+                            && !resolved.enabled_through(&name, &sub)
+                        {
+                            resolved.implied.push((sub.clone(), name.clone()));
+                            pending.push(sub);
+                        }
+                    }
+                    dependency => {
+                        if !resolved.dependencies.contains(&dependency) {
+                            resolved.dependencies.push(dependency);
+                        }
+                    }
+                }
+            }
+        }
+        resolved
+    }
+
+    /// Every local feature a build with the selection plus `extra` enables,
+    /// including implied and default features.
+    pub fn enabled_features(&self, extra: &[String]) -> Vec<String> {
+        let mut enabled = self.selected_features();
+        for feature in extra {
+            if !enabled.contains(feature) {
+                enabled.push(feature.clone());
+            }
+        }
+        let implied = self.resolve_features(&enabled).implied;
+        for feature in implied
+            .into_iter()
+            .map(|(name, _)| name)
+            .chain(self.default_features())
+        {
+            if !enabled.contains(&feature) {
+                enabled.push(feature);
+            }
+        }
+        enabled
+    }
+
+    /// One message per feature group with more than one feature enabled by a
+    /// build with the selection plus `extra`.
+    pub fn group_violations(&self, extra: &[String]) -> Vec<String> {
+        let enabled = self.enabled_features(extra);
+        self.feature_groups
+            .iter()
+            .filter_map(|group| {
+                let on = group
+                    .features
+                    .iter()
+                    .filter(|f| enabled.contains(f))
+                    .map(String::as_str)
+                    .collect::<Vec<_>>();
+                (on.len() > 1).then(|| {
+                    format!(
+                        "{}: {} can't be enabled together (group '{}')",
+                        self.name,
+                        on.join(" and "),
+                        group.name
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// The program's own features that are on because of `default`. Empty
+    /// with `no_default_features`.
+    pub fn default_features(&self) -> Vec<String> {
+        if self.no_default_features {
+            return Vec::new();
+        }
+        self.resolve_features(&["default".to_string()])
+            .implied
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresetProgram {
+    pub name: String,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_default_features: bool,
+}
+
+/// Which presets file a preset is stored in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PresetOrigin {
+    /// `.sbt/presets.toml`, committed for the whole team.
+    #[default]
+    Shared,
+    /// `.sbt/presets.local.toml`, private to this checkout.
+    Personal,
+}
+
+impl PresetOrigin {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            PresetOrigin::Shared => "👥",
+            PresetOrigin::Personal => "👤",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PresetOrigin::Shared => "Shared",
+            PresetOrigin::Personal => "Personal",
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Preset {
     pub name: String,
-    pub programs: Vec<(String, Vec<String>)>,
+    /// Set from the file the preset was loaded from, not stored in it.
+    #[serde(skip)]
+    pub origin: PresetOrigin,
+    /// Build as soon as the preset is clicked instead of only selecting it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub run_on_click: bool,
+    /// TypeScript IDL output directory for this preset's builds; the one from
+    /// the Options window is used when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_dir: Option<String>,
+    /// Name of a build profile whose features and arguments are added to
+    /// this preset's builds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Extra arguments for `anchor build`, after the profile's.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anchor_args: Vec<String>,
+    /// Extra arguments passed through to cargo, after the profile's.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cargo_args: Vec<String>,
+    /// Environment variables set for the build, overriding the profile's.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Programs in the order they are built.
+    #[serde(default)]
+    pub programs: Vec<PresetProgram>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobOutcome {
+    Succeeded,
+    Failed,
+    Cancelled,
+    /// The build command could not be started at all.
+    Error(String),
+}
+
+/// A file produced by a build, with its size in bytes.
+#[derive(Clone, Debug, Serialize)]
+pub struct Artifact {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Progress reported by a build thread. Every `JobStarted` is followed by
+/// zero or more `Output` lines and exactly one `JobFinished`; a build ends
+/// with either `AllDone` or `Cancelled`.
+#[derive(Clone, Debug)]
+pub enum BuildEvent {
+    JobStarted {
+        program: String,
+        features: Vec<String>,
+        no_default_features: bool,
+        command: String,
+        cwd: PathBuf,
+    },
+    Output {
+        stream: OutputStream,
+        line: String,
+    },
+    JobFinished {
+        program: String,
+        outcome: JobOutcome,
+        exit_code: Option<i32>,
+        duration: Duration,
+        artifacts: Vec<Artifact>,
+        idl: Vec<PathBuf>,
+    },
+    /// A program was not started because the build was cancelled first.
+    JobSkipped {
+        program: String,
+    },
+    AllDone,
+    Cancelled,
+}
+
+impl BuildEvent {
+    /// Whether this event ends the build it belongs to.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, BuildEvent::AllDone | BuildEvent::Cancelled)
+    }
+
+    /// Text shown for this event in the Build Output log.
+    pub fn log_line(&self) -> String {
+        match self {
+            BuildEvent::JobStarted { command, cwd, .. } => {
+                format!("Running: {} (from {})", command, cwd.display())
+            }
+            BuildEvent::Output { line, .. } => line.clone(),
+            BuildEvent::JobFinished {
+                program,
+                outcome,
+                exit_code,
+                duration,
+                ..
+            } => match outcome {
+                JobOutcome::Succeeded => format!(
+                    "{}: build succeeded in {:.1}s.",
+                    program,
+                    duration.as_secs_f32()
+                ),
+                JobOutcome::Failed => format!(
+                    "{}: build failed with code {:?} after {:.1}s.",
+                    program,
+                    exit_code,
+                    duration.as_secs_f32()
+                ),
+                JobOutcome::Cancelled => format!("{}: build cancelled.", program),
+                JobOutcome::Error(e) => format!("{}: command failed: {}", program, e),
+            },
+            BuildEvent::JobSkipped { program } => format!("Skipped {} (cancelled).", program),
+            BuildEvent::AllDone => "Build complete.".to_string(),
+            BuildEvent::Cancelled => "Build cancelled by user.".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    Skipped,
+}
+
+impl From<&JobOutcome> for BuildStatus {
+    fn from(outcome: &JobOutcome) -> Self {
+        match outcome {
+            JobOutcome::Succeeded => BuildStatus::Succeeded,
+            JobOutcome::Failed | JobOutcome::Error(_) => BuildStatus::Failed,
+            JobOutcome::Cancelled => BuildStatus::Cancelled,
+        }
+    }
+}
+
+/// Last known build result for one program, shown next to it in the Programs pane.
+#[derive(Clone, Debug)]
+pub struct ProgramBuildStatus {
+    pub status: BuildStatus,
+    pub started: Instant,
+    pub duration: Option<Duration>,
+    pub finished_at: Option<DateTime<Local>>,
+}
+
+impl ProgramBuildStatus {
+    pub fn running() -> Self {
+        Self {
+            status: BuildStatus::Running,
+            started: Instant::now(),
+            duration: None,
+            finished_at: None,
+        }
+    }
+
+    pub fn finish(&mut self, outcome: &JobOutcome, duration: Duration) {
+        self.status = outcome.into();
+        self.duration = Some(duration);
+        self.finished_at = Some(Local::now());
+    }
+
+    pub fn skipped() -> Self {
+        Self {
+            status: BuildStatus::Skipped,
+            started: Instant::now(),
+            duration: None,
+            finished_at: Some(Local::now()),
+        }
+    }
+
+    /// Wall-clock time spent so far, or in total once the build has finished.
+    pub fn elapsed(&self) -> Duration {
+        self.duration.unwrap_or_else(|| self.started.elapsed())
+    }
+}
+
+/// A build requested from the UI. Programs are snapshotted when the job is
+/// queued, so later selection changes don't affect it.
+#[derive(Clone)]
+pub enum BuildJob {
+    Selected {
+        programs: Vec<Program>,
+        build_dir: Option<String>,
+    },
+    All {
+        programs: Vec<Program>,
+        profile: BuildProfile,
+        build_dir: Option<String>,
+    },
+    /// The programs a preset selects in its order, with the profile, arguments
+    /// and environment it combines to.
+    Preset {
+        name: String,
+        programs: Vec<Program>,
+        profile: BuildProfile,
+        build_dir: Option<String>,
+    },
+}
+
+impl BuildJob {
+    pub fn label(&self) -> String {
+        match self {
+            BuildJob::Selected { programs, .. } => {
+                let names = programs
+                    .iter()
+                    .filter(|p| p.included)
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>();
+                format!("Build ({})", names.join(", "))
+            }
+            BuildJob::All { profile, .. } => format!("Build All ({})", profile.name),
+            BuildJob::Preset { name, .. } => format!("Preset ({})", name),
+        }
+    }
+
+    /// Feature groups the job would violate; such a job must not be run.
+    pub fn group_violations(&self) -> Vec<String> {
+        match self {
+            BuildJob::Selected { programs, .. } => programs
+                .iter()
+                .filter(|p| p.included)
+                .flat_map(|p| p.group_violations(&[]))
+                .collect(),
+            BuildJob::Preset {
+                programs, profile, ..
+            } => programs
+                .iter()
+                .filter(|p| p.included)
+                .flat_map(|p| p.group_violations(&profile.features_for(p)))
+                .collect(),
+            BuildJob::All {
+                programs, profile, ..
+            } => programs
+                .iter()
+                .flat_map(|p| {
+                    // Profile builds ignore the feature selection.
+                    let mut program = p.clone();
+                    program.selected.iter_mut().for_each(|s| *s = false);
+                    program.group_violations(&profile.features_for(p))
+                })
+                .collect(),
+        }
+    }
+}
+
+pub struct RunningJob {
+    pub label: String,
+    pub cancel: CancelToken,
+}
+
+/// Builds share one workspace and `target/deploy`, so only one job runs at a
+/// time; the rest wait in a FIFO queue.
+#[derive(Default)]
+pub struct BuildState {
+    pub running: Option<RunningJob>,
+    pub queue: VecDeque<BuildJob>,
+}
+
+impl BuildState {
+    pub fn is_busy(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Starts the next queued job if nothing is running. Returns whether a job
+    /// was started.
+    pub fn start_next(&mut self, tx: &BuildSender) -> bool {
+        if self.running.is_some() {
+            return false;
+        }
+        let Some(job) = self.queue.pop_front() else {
+            return false;
+        };
+        let label = job.label();
+        let cancel = spawn_job(job, tx.clone());
+        self.running = Some(RunningJob { label, cancel });
+        true
+    }
 }
 
 pub struct BuildTool {
+    /// Directory the user opened; programs are scanned below it.
+    pub workspace_root: PathBuf,
+    /// Root of the enclosing cargo workspace, holding `.sbt/` config and presets.
+    pub config_root: PathBuf,
+    pub scan_error: Option<ScanError>,
     pub programs: Vec<Program>,
     pub selected_program: Option<usize>,
     pub build_output: String,
-    pub build_rx: Receiver<String>,
-    pub build_tx: Sender<String>,
+    pub build_rx: Receiver<BuildEvent>,
+    pub build_tx: Sender<BuildEvent>,
     pub build_dir: Option<String>,
+    pub build_state: BuildState,
+    pub build_status: HashMap<String, ProgramBuildStatus>,
+    pub report_builder: ReportBuilder,
+    pub last_report: Option<BuildReport>,
     pub presets: Vec<Preset>,
+    pub preset_error: Option<PresetError>,
+    pub settings: Settings,
+    pub config: WorkspaceConfig,
+    pub config_error: Option<String>,
+    /// A profile build waiting for the user to confirm that some programs
+    /// lack features the profile enables.
+    pub pending_profile_build: Option<(BuildJob, Vec<String>)>,
+    /// Feature group conflicts that stopped the last requested build.
+    pub blocked_build: Option<Vec<String>>,
+    /// A build whose commands are shown without running it.
+    pub plan_preview: Option<BuildJob>,
+    pub preset_editor: Option<PresetEditor>,
+    /// What each entry of `presets` names that the workspace lacks.
+    pub preset_issues: Vec<PresetIssues>,
+    /// A stale preset being fixed in the repair window.
+    pub preset_repair: Option<(usize, PresetRepair)>,
+}
+
+/// A copy of `presets[index]` being changed in the preset editor; it replaces
+/// the original only when saved.
+pub struct PresetEditor {
+    pub index: usize,
+    pub draft: Preset,
+    /// Text being edited for the draft's shell-quoted arguments and
+    /// `KEY=VALUE` environment lines; parsed back by `finish`.
+    pub anchor_args: String,
+    pub cargo_args: String,
+    pub env: String,
+}
+
+impl PresetEditor {
+    pub fn new(index: usize, preset: &Preset) -> Self {
+        PresetEditor {
+            index,
+            draft: preset.clone(),
+            anchor_args: join_args(&preset.anchor_args),
+            cargo_args: join_args(&preset.cargo_args),
+            env: preset
+                .env
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// The edited preset, or why it can't be saved.
+    pub fn finish(&self) -> Result<Preset, String> {
+        if self.draft.name.trim().is_empty() {
+            return Err("The preset needs a name".to_string());
+        }
+        let mut env = BTreeMap::new();
+        for line in self.env.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("Expected KEY=VALUE, got '{}'", line));
+            };
+            let key = key.trim();
+            if !is_env_name(key) {
+                return Err(format!(
+                    "'{}' is not a valid variable name (letters, digits and _, not starting with a digit)",
+                    key
+                ));
+            }
+            env.insert(key.to_string(), value.trim().to_string());
+        }
+        let anchor_args =
+            shell_split(&self.anchor_args).map_err(|e| format!("Anchor args: {}", e))?;
+        let cargo_args = shell_split(&self.cargo_args).map_err(|e| format!("Cargo args: {}", e))?;
+        Ok(Preset {
+            anchor_args,
+            cargo_args,
+            env,
+            ..self.draft.clone()
+        })
+    }
+}
+
+/// `args` as editable text that `shell_split` turns back into the same list.
+fn join_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether `name` is a portable environment variable name, so it can be set
+/// as `NAME=value` in a shell: `[A-Za-z_][A-Za-z0-9_]*`.
+fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl BuildTool {
+    /// Switches to the workspace at `root`: rescans its programs, loads its
+    /// presets and records it in the recent workspaces list.
+    pub fn open_workspace(&mut self, root: PathBuf) {
+        let switched = root != self.workspace_root;
+        if switched {
+            self.store_workspace_settings();
+        }
+        self.workspace_root = root;
+        if switched {
+            self.programs.clear();
+            self.build_status.clear();
+        }
+        self.rescan();
+        if switched {
+            self.restore_workspace_settings();
+        }
+        // The editor and repair window refer to presets by index, which a
+        // reload invalidates.
+        self.preset_editor = None;
+        self.preset_repair = None;
+        self.plan_preview = None;
+        match load_presets(&self.config_root, &self.workspace_root) {
+            Ok(presets) => {
+                self.presets = presets;
+                self.preset_error = None;
+            }
+            Err(e) => {
+                self.presets = Vec::new();
+                self.preset_error = Some(e);
+            }
+        }
+        self.validate_presets();
+        match WorkspaceConfig::load(&self.config_root) {
+            Ok(config) => {
+                self.config = config;
+                self.config_error = None;
+            }
+            Err(e) => {
+                self.config = WorkspaceConfig::default();
+                self.config_error = Some(e);
+            }
+        }
+        if self.scan_error.is_none() {
+            self.settings.add_recent_workspace(&self.workspace_root);
+            self.settings.save();
+        }
+    }
+
+    /// Adds `preset` after the last preset of its origin, keeping the list in
+    /// the order `load_presets` returns it: shared first, then personal.
+    pub fn insert_preset(&mut self, preset: Preset) {
+        let idx = match preset.origin {
+            PresetOrigin::Shared => self.shared_preset_count(),
+            PresetOrigin::Personal => self.presets.len(),
+        };
+        self.insert_preset_at(idx, preset);
+    }
+
+    pub fn insert_preset_at(&mut self, idx: usize, preset: Preset) {
+        self.presets.insert(idx, preset);
+        self.remap_preset_windows(|i| Some(if i >= idx { i + 1 } else { i }));
+    }
+
+    /// Removes the preset at `idx`, closing the windows open on it.
+    pub fn remove_preset(&mut self, idx: usize) -> Preset {
+        let preset = self.presets.remove(idx);
+        self.remap_preset_windows(|i| match i.cmp(&idx) {
+            std::cmp::Ordering::Less => Some(i),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(i - 1),
+        });
+        preset
+    }
+
+    /// Moves the preset at `from` so it ends up at `to`.
+    pub fn move_preset(&mut self, from: usize, to: usize) {
+        let preset = self.presets.remove(from);
+        self.presets.insert(to, preset);
+        self.remap_preset_windows(|i| {
+            Some(if i == from {
+                to
+            } else {
+                let i = if i > from { i - 1 } else { i };
+                if i >= to {
+                    i + 1
+                } else {
+                    i
+                }
+            })
+        });
+    }
+
+    /// Turns the personal preset at `idx` into the last shared one.
+    pub fn promote_preset(&mut self, idx: usize) {
+        let to = self.shared_preset_count();
+        self.presets[idx].origin = PresetOrigin::Shared;
+        self.move_preset(idx, to);
+    }
+
+    fn shared_preset_count(&self) -> usize {
+        self.presets
+            .iter()
+            .filter(|p| p.origin == PresetOrigin::Shared)
+            .count()
+    }
+
+    /// The editor and repair window refer to their preset by position; follow
+    /// the preset when the list changes, and close them if `map` drops it.
+    fn remap_preset_windows(&mut self, map: impl Fn(usize) -> Option<usize>) {
+        self.preset_editor = self.preset_editor.take().and_then(|mut editor| {
+            editor.index = map(editor.index)?;
+            Some(editor)
+        });
+        self.preset_repair = self
+            .preset_repair
+            .take()
+            .and_then(|(index, repair)| Some((map(index)?, repair)));
+    }
+
+    /// Writes `presets` to the workspace presets file. Refuses to when that
+    /// file failed to load, so a file with a typo isn't replaced wholesale.
+    pub fn save_presets(&mut self) {
+        self.validate_presets();
+        if self
+            .preset_error
+            .as_ref()
+            .is_some_and(PresetError::is_load_error)
+        {
+            return;
+        }
+        self.preset_error = save_presets(&self.config_root, &self.presets).err();
+    }
+
+    /// Checks every preset against the scanned programs. Nothing is flagged
+    /// while the scan has failed, since every program would look missing.
+    pub fn validate_presets(&mut self) {
+        if self.scan_error.is_some() {
+            self.preset_issues.clear();
+            return;
+        }
+        self.preset_issues = self
+            .presets
+            .iter()
+            .map(|preset| validate_preset(preset, &self.programs))
+            .collect();
+    }
+
+    /// Copies the current workspace's UI state into `settings`.
+    pub fn store_workspace_settings(&mut self) {
+        if self.workspace_root.as_os_str().is_empty() || self.scan_error.is_some() {
+            return;
+        }
+        let selected_features = self
+            .programs
+            .iter()
+            .map(|p| {
+                let features = p
+                    .features
+                    .iter()
+                    .zip(&p.selected)
+                    .filter(|(_, &sel)| sel)
+                    .map(|(f, _)| f.name.clone())
+                    .collect::<Vec<_>>();
+                (p.name.clone(), features)
+            })
+            .filter(|(_, features)| !features.is_empty())
+            .collect();
+        let included_programs = self
+            .programs
+            .iter()
+            .filter(|p| p.included)
+            .map(|p| p.name.clone())
+            .collect();
+        let no_default_features = self
+            .programs
+            .iter()
+            .filter(|p| p.no_default_features)
+            .map(|p| p.name.clone())
+            .collect();
+        let workspace = WorkspaceSettings {
+            build_dir: self.build_dir.clone(),
+            selected_program: self
+                .selected_program
+                .and_then(|i| self.programs.get(i))
+                .map(|p| p.name.clone()),
+            selected_features,
+            included_programs: Some(included_programs),
+            no_default_features,
+        };
+        self.settings
+            .workspaces
+            .insert(self.workspace_root.clone(), workspace);
+    }
+
+    /// Applies the UI state remembered for the current workspace.
+    fn restore_workspace_settings(&mut self) {
+        let workspace = self
+            .settings
+            .workspaces
+            .get(&self.workspace_root)
+            .cloned()
+            .unwrap_or_default();
+        self.build_dir = workspace.build_dir;
+        for program in &mut self.programs {
+            let selected = workspace.selected_features.get(&program.name);
+            program.selected = program
+                .features
+                .iter()
+                .map(|f| selected.is_some_and(|names| names.contains(&f.name)))
+                .collect();
+            // Settings from before inclusion was explicit built exactly the
+            // programs with selected features.
+            program.included = match &workspace.included_programs {
+                Some(included) => included.contains(&program.name),
+                None => program.selected.iter().any(|&s| s),
+            };
+            program.no_default_features = workspace.no_default_features.contains(&program.name);
+        }
+        self.selected_program = workspace
+            .selected_program
+            .and_then(|name| self.programs.iter().position(|p| p.name == name));
+    }
+
+    /// Queues a build of every program with `profile`, first asking for
+    /// confirmation if any program lacks one of the profile's features.
+    pub fn request_profile_build(&mut self, profile: &BuildProfile) {
+        let job = BuildJob::All {
+            programs: self.programs.clone(),
+            profile: profile.clone(),
+            build_dir: self.build_dir.clone(),
+        };
+        let missing: Vec<String> = self
+            .programs
+            .iter()
+            .filter_map(|program| {
+                let missing = profile.missing_features(program);
+                (!missing.is_empty()).then(|| format!("{}: {}", program.name, missing.join(", ")))
+            })
+            .collect();
+        if missing.is_empty() {
+            self.enqueue_build(job);
+        } else {
+            self.pending_profile_build = Some((job, missing));
+        }
+    }
+
+    /// Rescans `workspace_root`, keeping the feature selection of programs
+    /// that are still present. On failure the program list is cleared and the
+    /// error kept for display.
+    pub fn rescan(&mut self) {
+        let old_programs = std::mem::take(&mut self.programs);
+        self.selected_program = None;
+        match scan_programs(&self.workspace_root) {
+            Ok(workspace) => {
+                self.config_root = workspace.root;
+                self.programs = workspace.programs;
+                self.scan_error = None;
+            }
+            Err(e) => {
+                self.config_root = self.workspace_root.clone();
+                self.scan_error = Some(e);
+                self.validate_presets();
+                return;
+            }
+        }
+        for new_program in &mut self.programs {
+            if let Some(old_program) = old_programs.iter().find(|p| p.name == new_program.name) {
+                new_program.included = old_program.included;
+                new_program.no_default_features = old_program.no_default_features;
+                new_program.selected = new_program
+                    .features
+                    .iter()
+                    .map(|f| {
+                        old_program
+                            .features
+                            .iter()
+                            .position(|of| of.name == f.name)
+                            .map(|i| old_program.selected.get(i).copied().unwrap_or(false))
+                            .unwrap_or(false)
+                    })
+                    .collect();
+            }
+        }
+        self.validate_presets();
+    }
+
+    /// Queues `job`, starting it right away when no other build is running.
+    /// A job that enables conflicting features is refused and the conflicts
+    /// kept in `blocked_build` for display.
+    pub fn enqueue_build(&mut self, job: BuildJob) {
+        let violations = job.group_violations();
+        if !violations.is_empty() {
+            self.blocked_build = Some(violations);
+            return;
+        }
+        if !self.build_state.is_busy() && self.build_state.queue.is_empty() {
+            self.build_output.clear();
+        }
+        self.build_state.queue.push_back(job);
+        self.build_state.start_next(&self.build_tx);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// An Anchor program `foo` with the given `[features]` entries, none of
+    /// them selected. Shared by the tests of other modules.
+    pub fn program(features: &[(&str, &[&str])]) -> Program {
+        Program {
+            name: "foo".to_string(),
+            features: features
+                .iter()
+                .map(|(name, sub_features)| Feature {
+                    name: name.to_string(),
+                    sub_features: sub_features.iter().map(|s| s.to_string()).collect(),
+                    description: None,
+                })
+                .collect(),
+            selected: vec![false; features.len()],
+            included: false,
+            path: PathBuf::new(),
+            framework: Framework::Anchor,
+            lib_name: "foo".to_string(),
+            target_dir: PathBuf::new(),
+            no_default_features: false,
+            feature_groups: Vec::new(),
+        }
+    }
+
+    fn names(features: &[&str]) -> Vec<String> {
+        features.iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn preset_editor_keeps_args_with_spaces() {
+        let preset = Preset {
+            name: "release".to_string(),
+            anchor_args: vec!["--skip-lint".to_string()],
+            cargo_args: vec![
+                "--config".to_string(),
+                "build.rustflags = ['-C', 'debuginfo=0']".to_string(),
+            ],
+            ..Default::default()
+        };
+        let mut editor = PresetEditor::new(0, &preset);
+        editor.draft.name = "renamed".to_string();
+        let edited = editor.finish().unwrap();
+        assert_eq!(edited.anchor_args, preset.anchor_args);
+        assert_eq!(edited.cargo_args, preset.cargo_args);
+
+        editor.cargo_args = "--config 'unterminated".to_string();
+        assert!(editor.finish().is_err());
+    }
+
+    #[test]
+    fn preset_editor_rejects_bad_env_names() {
+        let mut editor = PresetEditor::new(
+            0,
+            &Preset {
+                name: "release".to_string(),
+                ..Default::default()
+            },
+        );
+        editor.env = "RUSTFLAGS=-C debuginfo=0\n_X1 = spaced".to_string();
+        let preset = editor.finish().unwrap();
+        assert_eq!(preset.env["RUSTFLAGS"], "-C debuginfo=0");
+        assert_eq!(preset.env["_X1"], "spaced");
+
+        for line in ["MY VAR=1", "$HOME=1", "1X=1", "=1", "NOEQUALS"] {
+            editor.env = line.to_string();
+            assert!(editor.finish().is_err(), "{} was accepted", line);
+        }
+    }
+
+    #[test]
+    fn feature_refs_parse_dependencies() {
+        assert_eq!(
+            FeatureRef::parse("prod"),
+            FeatureRef::Local("prod".to_string())
+        );
+        assert_eq!(
+            FeatureRef::parse("dep:serde"),
+            FeatureRef::Dependency("serde".to_string())
+        );
+        assert_eq!(
+            FeatureRef::parse("anchor-lang/init-if-needed"),
+            FeatureRef::DependencyFeature {
+                krate: "anchor-lang".to_string(),
+                feature: "init-if-needed".to_string(),
+                weak: false,
+            }
+        );
+        assert_eq!(
+            FeatureRef::parse("serde?/std"),
+            FeatureRef::DependencyFeature {
+                krate: "serde".to_string(),
+                feature: "std".to_string(),
+                weak: true,
+            }
+        );
+    }
+
+    #[test]
+    fn resolve_follows_implied_features() {
+        let program = program(&[
+            ("release", &["prod", "dep:serde"]),
+            ("prod", &["anchor-lang/prod", "missing"]),
+            ("local", &[]),
+        ]);
+        let resolved = program.resolve_features(&names(&["release"]));
+        assert_eq!(
+            resolved.implied,
+            vec![("prod".to_string(), "release".to_string())]
+        );
+        assert_eq!(
+            resolved.dependencies,
+            vec![
+                FeatureRef::Dependency("serde".to_string()),
+                FeatureRef::parse("anchor-lang/prod"),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_terminates_on_cycles() {
+        let program = program(&[("a", &["b"]), ("b", &["a"])]);
+        let resolved = program.resolve_features(&names(&["a"]));
+        assert_eq!(resolved.implied, vec![("b".to_string(), "a".to_string())]);
+        assert_eq!(resolved.enabler_of("a"), None);
+
+        let mut program = program;
+        program.selected = vec![true, false];
+        assert_eq!(program.enabled_features(&[]), names(&["a", "b"]));
+    }
+
+    #[test]
+    fn resolve_credits_a_checked_feature_to_its_enabler() {
+        let program = program(&[("slow", &["prod"]), ("prod", &[])]);
+        let resolved = program.resolve_features(&names(&["prod", "slow"]));
+        assert_eq!(resolved.enabler_of("prod"), Some("slow"));
+    }
+
+    /// `foo` with `local` on by default, `slow` implying `prod`, and `local`
+    /// and `prod` in an exclusive group.
+    fn grouped_program() -> Program {
+        let mut program = program(&[
+            ("default", &["local"]),
+            ("local", &[]),
+            ("prod", &[]),
+            ("slow", &["prod"]),
+        ]);
+        program.feature_groups = vec![FeatureGroup {
+            name: "cluster".to_string(),
+            features: names(&["local", "prod"]),
+        }];
+        program
+    }
+
+    fn select(program: &mut Program, features: &[&str]) {
+        for (feature, selected) in program.features.iter().zip(&mut program.selected) {
+            *selected = features.contains(&feature.name.as_str());
+        }
+        program.included = true;
+    }
+
+    #[test]
+    fn group_violations_count_default_features() {
+        let mut program = grouped_program();
+        select(&mut program, &["prod"]);
+        assert_eq!(
+            program.group_violations(&[]),
+            ["foo: local and prod can't be enabled together (group 'cluster')"]
+        );
+
+        program.no_default_features = true;
+        assert!(program.group_violations(&[]).is_empty());
+    }
+
+    #[test]
+    fn group_violations_count_implied_and_extra_features() {
+        let mut program = grouped_program();
+        program.no_default_features = true;
+        select(&mut program, &["local", "slow"]);
+        assert_eq!(program.group_violations(&[]).len(), 1);
+
+        select(&mut program, &["local"]);
+        assert!(program.group_violations(&[]).is_empty());
+        assert_eq!(program.group_violations(&names(&["prod"])).len(), 1);
+    }
+
+    #[test]
+    fn job_group_violations_follow_what_each_job_builds() {
+        let mut program = grouped_program();
+        program.no_default_features = true;
+        select(&mut program, &["local"]);
+        let mut prod = BuildProfile::empty("Prod");
+        prod.features = names(&["prod"]);
+
+        let selected = BuildJob::Selected {
+            programs: vec![program.clone()],
+            build_dir: None,
+        };
+        assert!(selected.group_violations().is_empty());
+
+        // A preset adds its profile's features to the selection.
+        let preset = BuildJob::Preset {
+            name: "release".to_string(),
+            programs: vec![program.clone()],
+            profile: prod.clone(),
+            build_dir: None,
+        };
+        assert_eq!(preset.group_violations().len(), 1);
+
+        // Building all programs ignores the selection.
+        let all = BuildJob::All {
+            programs: vec![program.clone()],
+            profile: prod.clone(),
+            build_dir: None,
+        };
+        assert!(all.group_violations().is_empty());
+
+        // Programs that aren't included don't count, except when building all.
+        program.included = false;
+        program.no_default_features = false;
+        let selected = BuildJob::Selected {
+            programs: vec![program.clone()],
+            build_dir: None,
+        };
+        assert!(selected.group_violations().is_empty());
+        let all = BuildJob::All {
+            programs: vec![program],
+            profile: prod,
+            build_dir: None,
+        };
+        assert_eq!(all.group_violations().len(), 1);
+    }
 }
@@ -0,0 +1,344 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::config::BuildProfile;
+use crate::model::{BuildJob, Framework, Program};
+
+/// The exact commands a [`BuildJob`] runs, in order. The executor runs these
+/// and the dry-run views print them, so what is shown is what runs.
+pub struct BuildPlan {
+    pub steps: Vec<PlannedBuild>,
+}
+
+/// One program's build command.
+pub struct PlannedBuild {
+    pub program: Program,
+    /// Features passed with `--features`.
+    pub features: Vec<String>,
+    pub executable: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    /// Variables set on top of sbt's own environment.
+    pub env: BTreeMap<String, String>,
+    /// TypeScript IDL output directory, where generated types are looked for.
+    pub build_dir: Option<String>,
+}
+
+impl BuildPlan {
+    pub fn new(job: &BuildJob) -> Self {
+        let steps = match job {
+            BuildJob::Selected {
+                programs,
+                build_dir,
+            } => {
+                let profile = BuildProfile::empty("Selected");
+                programs
+                    .iter()
+                    .filter(|p| p.included)
+                    .map(|p| PlannedBuild::new(p, p.selected_features(), &profile, build_dir))
+                    .collect()
+            }
+            BuildJob::Preset {
+                programs,
+                profile,
+                build_dir,
+                ..
+            } => programs
+                .iter()
+                .filter(|p| p.included)
+                .map(|p| {
+                    let mut features = p.selected_features();
+                    for feature in profile.features_for(p) {
+                        if !features.contains(&feature) {
+                            features.push(feature);
+                        }
+                    }
+                    PlannedBuild::new(p, features, profile, build_dir)
+                })
+                .collect(),
+            BuildJob::All {
+                programs,
+                profile,
+                build_dir,
+            } => programs
+                .iter()
+                .map(|p| PlannedBuild::new(p, profile.features_for(p), profile, build_dir))
+                .collect(),
+        };
+        BuildPlan { steps }
+    }
+
+    /// The whole plan as a shell script, one line per program.
+    pub fn shell_script(&self) -> String {
+        self.steps
+            .iter()
+            .map(PlannedBuild::shell_line)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl PlannedBuild {
+    /// Plans `anchor build` for Anchor programs and `cargo build-sbf` for
+    /// everything else. The profile's `anchor_args` only apply to Anchor
+    /// programs; its `cargo_args` are passed through to cargo either way,
+    /// after `--`, since neither tool accepts every cargo option itself.
+    fn new(
+        program: &Program,
+        features: Vec<String>,
+        profile: &BuildProfile,
+        build_dir: &Option<String>,
+    ) -> Self {
+        // Both tools understand the feature flags directly.
+        let mut feature_args = Vec::new();
+        if program.no_default_features {
+            feature_args.push("--no-default-features".to_string());
+        }
+        if !features.is_empty() {
+            feature_args.push("--features".to_string());
+            feature_args.push(features.join(","));
+        }
+
+        let (executable, args) = match program.framework {
+            Framework::Anchor => {
+                let mut args = vec!["build".to_string(), "-p".to_string(), program.name.clone()];
+                if let Some(dir) = build_dir {
+                    args.extend(["-t".to_string(), dir.clone()]);
+                }
+                args.extend(profile.anchor_args.iter().cloned());
+                // anchor only forwards cargo's feature flags after `--`.
+                let cargo_args = feature_args
+                    .into_iter()
+                    .chain(profile.cargo_args.iter().cloned())
+                    .collect::<Vec<_>>();
+                if !cargo_args.is_empty() {
+                    args.push("--".to_string());
+                    args.extend(cargo_args);
+                }
+                ("anchor", args)
+            }
+            Framework::Native | Framework::Pinocchio => {
+                let mut args = vec![
+                    "build-sbf".to_string(),
+                    "--manifest-path".to_string(),
+                    program
+                        .path
+                        .join("Cargo.toml")
+                        .to_string_lossy()
+                        .into_owned(),
+                ];
+                args.extend(feature_args);
+                if !profile.cargo_args.is_empty() {
+                    args.push("--".to_string());
+                    args.extend(profile.cargo_args.iter().cloned());
+                }
+                ("cargo", args)
+            }
+        };
+
+        PlannedBuild {
+            program: program.clone(),
+            features,
+            executable: executable.to_string(),
+            args,
+            cwd: program.path.clone(),
+            env: profile.env.clone(),
+            build_dir: build_dir.clone(),
+        }
+    }
+
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.executable);
+        command
+            .args(&self.args)
+            .current_dir(&self.cwd)
+            .envs(&self.env);
+        command
+    }
+
+    /// The environment overrides and command, shell-quoted, e.g.
+    /// `RUSTFLAGS='-C debuginfo=0' anchor build -p foo`.
+    pub fn command_line(&self) -> String {
+        self.env
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, shell_quote(value)))
+            .chain(std::iter::once(shell_quote(&self.executable)))
+            .chain(self.args.iter().map(|arg| shell_quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// `command_line` run from the build's working directory, ready to paste
+    /// into a shell.
+    pub fn shell_line(&self) -> String {
+        format!(
+            "(cd {} && {})",
+            shell_quote(&self.cwd.to_string_lossy()),
+            self.command_line()
+        )
+    }
+}
+
+/// Quotes `arg` for a POSIX shell, leaving it bare when that is safe.
+pub fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:,=+@%".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Splits `text` into words the way a POSIX shell would, honouring single
+/// and double quotes and backslash escapes; the inverse of joining
+/// `shell_quote`d words with spaces. Nothing is expanded.
+pub fn shell_split(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                words.extend(word.take());
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated ' quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated \" quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated \" quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::tests;
+
+    fn program(framework: Framework, path: &str) -> Program {
+        let mut program = tests::program(&[("prod", &[])]);
+        program.selected = vec![true];
+        program.included = true;
+        program.framework = framework;
+        program.path = PathBuf::from(path);
+        program
+    }
+
+    #[test]
+    fn shell_quote_leaves_safe_words_bare() {
+        assert_eq!(shell_quote("--features"), "--features");
+        assert_eq!(shell_quote("local,prod"), "local,prod");
+        assert_eq!(shell_quote("/tmp/ws/programs/foo"), "/tmp/ws/programs/foo");
+    }
+
+    #[test]
+    fn shell_quote_quotes_spaces_and_quotes() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("my dir"), "'my dir'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn shell_split_reverses_shell_quote() {
+        let args = vec![
+            "--config".to_string(),
+            "build.rustflags = ['-C', 'debuginfo=0']".to_string(),
+            String::new(),
+            "it's \"quoted\"".to_string(),
+            "plain".to_string(),
+        ];
+        let text = args
+            .iter()
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(shell_split(&text).unwrap(), args);
+    }
+
+    #[test]
+    fn shell_split_handles_shell_syntax() {
+        assert_eq!(
+            shell_split(r#"  a "b c" d\ e "x\"y" 'z'z  "#).unwrap(),
+            vec!["a", "b c", "d e", "x\"y", "zz"]
+        );
+        assert!(shell_split("'open").is_err());
+        assert!(shell_split("\"open").is_err());
+    }
+
+    #[test]
+    fn build_sbf_gets_cargo_args_after_separator() {
+        let mut profile = BuildProfile::empty("Release");
+        profile.cargo_args = vec!["--config".to_string(), "net.offline=true".to_string()];
+        let plan = BuildPlan::new(&BuildJob::All {
+            programs: vec![program(Framework::Native, "/ws/nat")],
+            profile,
+            build_dir: None,
+        });
+        assert_eq!(
+            plan.steps[0].args,
+            [
+                "build-sbf",
+                "--manifest-path",
+                "/ws/nat/Cargo.toml",
+                "--",
+                "--config",
+                "net.offline=true"
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_lines_carry_cwd_env_and_args() {
+        let mut profile = BuildProfile::empty("Release");
+        profile.cargo_args = vec!["--locked".to_string()];
+        profile
+            .env
+            .insert("RUSTFLAGS".to_string(), "-C debuginfo=0".to_string());
+        let plan = BuildPlan::new(&BuildJob::Preset {
+            name: "release".to_string(),
+            programs: vec![program(Framework::Anchor, "/ws/my programs/foo")],
+            profile,
+            build_dir: Some("app/idl".to_string()),
+        });
+        assert_eq!(
+            plan.shell_script(),
+            "(cd '/ws/my programs/foo' && RUSTFLAGS='-C debuginfo=0' anchor build -p foo \
+             -t app/idl -- --features prod --locked)"
+        );
+    }
+}
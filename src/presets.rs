@@ -0,0 +1,504 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::{BuildProfile, WorkspaceConfig, CONFIG_DIR};
+use crate::model::{BuildJob, Preset, PresetOrigin, PresetProgram, Program};
+
+/// Presets committed to the repository for the whole team.
+pub const SHARED_PRESETS_FILE: &str = "presets.toml";
+/// Presets private to one checkout; git-ignored via `.sbt/.gitignore`.
+pub const PERSONAL_PRESETS_FILE: &str = "presets.local.toml";
+/// Unversioned `Vec<Preset>` JSON written by earlier releases into the
+/// directory sbt was opened in.
+const LEGACY_PRESETS_FILE: &str = "presets.json";
+/// Bump when `Preset` changes incompatibly, and teach `migrate` the old shape.
+pub const PRESETS_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum PresetError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, String),
+    /// The file was written by a newer sbt.
+    UnsupportedVersion(PathBuf, u32),
+    Write(PathBuf, String),
+}
+
+impl PresetError {
+    /// Whether the presets file on disk could not be loaded, in which case it
+    /// must not be overwritten.
+    pub fn is_load_error(&self) -> bool {
+        !matches!(self, PresetError::Write(..))
+    }
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::Read(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            PresetError::Parse(path, e) => write!(f, "failed to parse {}: {}", path.display(), e),
+            PresetError::UnsupportedVersion(path, version) => write!(
+                f,
+                "{} has version {}, but this sbt only understands up to version {}",
+                path.display(),
+                version,
+                PRESETS_VERSION
+            ),
+            PresetError::Write(path, e) => write!(f, "failed to write {}: {}", path.display(), e),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PresetsFile {
+    version: u32,
+    #[serde(default)]
+    presets: Vec<Preset>,
+}
+
+#[derive(Deserialize)]
+struct LegacyPreset {
+    name: String,
+    programs: Vec<(String, Vec<String>)>,
+}
+
+impl From<LegacyPreset> for Preset {
+    fn from(legacy: LegacyPreset) -> Self {
+        Preset {
+            name: legacy.name,
+            origin: PresetOrigin::Personal,
+            programs: legacy
+                .programs
+                .into_iter()
+                .map(|(name, features)| PresetProgram {
+                    name,
+                    features,
+                    no_default_features: false,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+pub fn presets_path(root: &Path, origin: PresetOrigin) -> PathBuf {
+    let file = match origin {
+        PresetOrigin::Shared => SHARED_PRESETS_FILE,
+        PresetOrigin::Personal => PERSONAL_PRESETS_FILE,
+    };
+    root.join(CONFIG_DIR).join(file)
+}
+
+/// Loads the shared presets of the workspace at `root`, followed by the
+/// personal ones. If neither file exists yet, presets are migrated from a
+/// legacy `presets.json` in `legacy_dir` into the personal file.
+pub fn load_presets(root: &Path, legacy_dir: &Path) -> Result<Vec<Preset>, PresetError> {
+    let shared = load_file(root, PresetOrigin::Shared)?;
+    let personal = load_file(root, PresetOrigin::Personal)?;
+    if shared.is_none() && personal.is_none() {
+        let legacy = load_legacy(legacy_dir)?;
+        if !legacy.is_empty() {
+            write_file(root, PresetOrigin::Personal, &legacy)?;
+        }
+        return Ok(legacy);
+    }
+    Ok(shared.into_iter().chain(personal).flatten().collect())
+}
+
+/// Reads one presets file, or `None` if it doesn't exist.
+fn load_file(root: &Path, origin: PresetOrigin) -> Result<Option<Vec<Preset>>, PresetError> {
+    let path = presets_path(root, origin);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read_to_string(&path).map_err(|e| PresetError::Read(path.clone(), e))?;
+    let value: toml::Value =
+        toml::from_str(&data).map_err(|e| PresetError::Parse(path.clone(), e.to_string()))?;
+    let mut presets = migrate(&path, value)?;
+    for preset in &mut presets {
+        preset.origin = origin;
+    }
+    Ok(Some(presets))
+}
+
+fn load_legacy(legacy_dir: &Path) -> Result<Vec<Preset>, PresetError> {
+    let legacy_path = legacy_dir.join(LEGACY_PRESETS_FILE);
+    if !legacy_path.exists() {
+        return Ok(Vec::new());
+    }
+    let data =
+        fs::read_to_string(&legacy_path).map_err(|e| PresetError::Read(legacy_path.clone(), e))?;
+    let legacy: Vec<LegacyPreset> = serde_json::from_str(&data)
+        .map_err(|e| PresetError::Parse(legacy_path.clone(), e.to_string()))?;
+    Ok(legacy.into_iter().map(Preset::from).collect())
+}
+
+/// Upgrades a parsed presets file to the current `Preset` shape.
+fn migrate(path: &Path, value: toml::Value) -> Result<Vec<Preset>, PresetError> {
+    let version = value
+        .get("version")
+        .and_then(toml::Value::as_integer)
+        .ok_or_else(|| PresetError::Parse(path.to_path_buf(), "missing `version`".to_string()))?;
+    let version = u32::try_from(version)
+        .map_err(|_| PresetError::Parse(path.to_path_buf(), "invalid `version`".to_string()))?;
+    if version > PRESETS_VERSION {
+        return Err(PresetError::UnsupportedVersion(path.to_path_buf(), version));
+    }
+
+    let file: PresetsFile = value
+        .try_into()
+        .map_err(|e: toml::de::Error| PresetError::Parse(path.to_path_buf(), e.to_string()))?;
+    Ok(file.presets)
+}
+
+/// Writes each preset to the shared or personal file according to its origin.
+/// A file is only created once it has presets to hold, and only rewritten when
+/// its presets changed, so editing personal presets leaves the comments and
+/// formatting of the committed shared file alone.
+pub fn save_presets(root: &Path, presets: &[Preset]) -> Result<(), PresetError> {
+    for origin in [PresetOrigin::Shared, PresetOrigin::Personal] {
+        let matching: Vec<Preset> = presets
+            .iter()
+            .filter(|p| p.origin == origin)
+            .cloned()
+            .collect();
+        let changed = match load_file(root, origin)? {
+            Some(saved) => saved != matching,
+            None => !matching.is_empty(),
+        };
+        if changed {
+            write_file(root, origin, &matching)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_file(root: &Path, origin: PresetOrigin, presets: &[Preset]) -> Result<(), PresetError> {
+    let path = presets_path(root, origin);
+    let write_error = |e: String| PresetError::Write(path.clone(), e);
+    let file = PresetsFile {
+        version: PRESETS_VERSION,
+        presets: presets.to_vec(),
+    };
+    let data = toml::to_string_pretty(&file).map_err(|e| write_error(e.to_string()))?;
+    let dir = root.join(CONFIG_DIR);
+    fs::create_dir_all(&dir).map_err(|e| write_error(e.to_string()))?;
+    if origin == PresetOrigin::Personal {
+        ignore_personal_presets(&dir).map_err(|e| write_error(e.to_string()))?;
+    }
+    fs::write(&path, data).map_err(|e| write_error(e.to_string()))
+}
+
+/// Makes sure `dir/.gitignore` lists the personal presets file, appending it
+/// to a `.gitignore` the team may already commit.
+fn ignore_personal_presets(dir: &Path) -> io::Result<()> {
+    let gitignore = dir.join(".gitignore");
+    let existing = match fs::read_to_string(&gitignore) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let listed = existing
+        .lines()
+        .map(|line| line.trim().trim_start_matches('/'))
+        .any(|line| line == PERSONAL_PRESETS_FILE);
+    if listed {
+        return Ok(());
+    }
+    let mut updated = existing;
+    if !updated.is_empty() && !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated.push_str(PERSONAL_PRESETS_FILE);
+    updated.push('\n');
+    fs::write(&gitignore, updated)
+}
+
+/// Programs and features a preset names that the workspace doesn't have.
+#[derive(Default)]
+pub struct PresetIssues {
+    pub missing_programs: Vec<String>,
+    /// `(program, feature)` pairs for programs that exist but no longer
+    /// define the feature.
+    pub missing_features: Vec<(String, String)>,
+}
+
+impl PresetIssues {
+    pub fn is_empty(&self) -> bool {
+        self.missing_programs.is_empty() && self.missing_features.is_empty()
+    }
+
+    /// One human-readable line per missing program or feature.
+    pub fn describe(&self) -> Vec<String> {
+        self.missing_programs
+            .iter()
+            .map(|program| format!("program '{}' not found", program))
+            .chain(
+                self.missing_features
+                    .iter()
+                    .map(|(program, feature)| format!("{} has no feature '{}'", program, feature)),
+            )
+            .collect()
+    }
+}
+
+/// Checks every program and feature named by `preset` against `programs`.
+pub fn validate_preset(preset: &Preset, programs: &[Program]) -> PresetIssues {
+    let mut issues = PresetIssues::default();
+    for entry in &preset.programs {
+        let Some(program) = programs.iter().find(|p| p.name == entry.name) else {
+            issues.missing_programs.push(entry.name.clone());
+            continue;
+        };
+        for feature in &entry.features {
+            if !program.features.iter().any(|f| f.name == *feature) {
+                issues
+                    .missing_features
+                    .push((entry.name.clone(), feature.clone()));
+            }
+        }
+    }
+    issues
+}
+
+/// How to fix a stale preset: each missing name is either remapped to an
+/// existing one or, when `None`, removed from the preset.
+pub struct PresetRepair {
+    pub programs: Vec<(String, Option<String>)>,
+    pub features: Vec<(String, String, Option<String>)>,
+}
+
+impl PresetRepair {
+    /// A repair that prunes everything listed in `issues`.
+    pub fn prune(issues: &PresetIssues) -> Self {
+        PresetRepair {
+            programs: issues
+                .missing_programs
+                .iter()
+                .map(|program| (program.clone(), None))
+                .collect(),
+            features: issues
+                .missing_features
+                .iter()
+                .map(|(program, feature)| (program.clone(), feature.clone(), None))
+                .collect(),
+        }
+    }
+
+    pub fn apply(&self, preset: &mut Preset) {
+        for (program, feature, replacement) in &self.features {
+            let Some(entry) = preset.programs.iter_mut().find(|e| e.name == *program) else {
+                continue;
+            };
+            match replacement {
+                Some(new) if !entry.features.contains(new) => {
+                    for f in entry.features.iter_mut().filter(|f| *f == feature) {
+                        *f = new.clone();
+                    }
+                }
+                _ => entry.features.retain(|f| f != feature),
+            }
+        }
+        for (program, replacement) in &self.programs {
+            match replacement {
+                Some(new) if !preset.programs.iter().any(|e| e.name == *new) => {
+                    for entry in preset.programs.iter_mut().filter(|e| e.name == *program) {
+                        entry.name = new.clone();
+                    }
+                }
+                _ => preset.programs.retain(|e| e.name != *program),
+            }
+        }
+    }
+}
+
+/// Includes exactly the programs named in `preset` and replaces their feature
+/// selection; other programs keep their features but are left out of "Build".
+/// Programs and features the preset mentions but the workspace lacks are
+/// ignored; `validate_preset` reports them.
+pub fn apply_preset(programs: &mut [Program], preset: &Preset) {
+    for program in programs.iter_mut() {
+        program.included = false;
+    }
+    for entry in &preset.programs {
+        if let Some(program) = programs.iter_mut().find(|p| p.name == entry.name) {
+            program.included = true;
+            program.no_default_features = entry.no_default_features;
+            program.selected.clear();
+            program.selected.resize(program.features.len(), false);
+            for feature in &entry.features {
+                if let Some(idx) = program.features.iter().position(|f| f.name == *feature) {
+                    program.selected[idx] = true;
+                }
+            }
+        }
+    }
+}
+
+/// The job that builds exactly the programs in `preset`, in the preset's
+/// order, with its selection, profile, arguments, environment and IDL
+/// directory. `default_build_dir` is used when the preset doesn't set one.
+pub fn preset_job(
+    programs: &[Program],
+    preset: &Preset,
+    config: &WorkspaceConfig,
+    default_build_dir: Option<String>,
+) -> Result<BuildJob, String> {
+    let mut profile = match &preset.profile {
+        Some(name) => config
+            .profile(name)
+            .cloned()
+            .ok_or_else(|| format!("preset '{}' uses unknown profile '{}'", preset.name, name))?,
+        None => BuildProfile::empty(&preset.name),
+    };
+    profile
+        .anchor_args
+        .extend(preset.anchor_args.iter().cloned());
+    profile.cargo_args.extend(preset.cargo_args.iter().cloned());
+    profile
+        .env
+        .extend(preset.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+    let mut programs: Vec<Program> = preset
+        .programs
+        .iter()
+        .filter_map(|entry| programs.iter().find(|p| p.name == entry.name).cloned())
+        .collect();
+    apply_preset(&mut programs, preset);
+    Ok(BuildJob::Preset {
+        name: preset.name.clone(),
+        programs,
+        profile,
+        build_dir: preset.build_dir.clone().or(default_build_dir),
+    })
+}
+
+/// Feature group conflicts a build of `preset` would have; a preset with any
+/// can't be saved.
+pub fn preset_violations(
+    programs: &[Program],
+    preset: &Preset,
+    config: &WorkspaceConfig,
+) -> Vec<String> {
+    preset_job(programs, preset, config, None)
+        .map(|job| job.group_violations())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory under the system temp dir, unique to `name`.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sbt-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// One line per preset with its origin, programs and features, for
+    /// comparing loads.
+    fn contents(presets: &[Preset]) -> Vec<String> {
+        presets
+            .iter()
+            .map(|preset| {
+                let programs = preset
+                    .programs
+                    .iter()
+                    .map(|p| format!("{}[{}]", p.name, p.features.join(",")))
+                    .collect::<Vec<_>>();
+                format!(
+                    "{} ({}): {}",
+                    preset.name,
+                    preset.origin.label(),
+                    programs.join(" ")
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn legacy_presets_migrate_to_the_personal_file() {
+        let root = scratch_dir("legacy");
+        fs::write(
+            root.join(LEGACY_PRESETS_FILE),
+            r#"[{"name": "release", "programs": [["foo", ["prod"]], ["bar", []]]}]"#,
+        )
+        .unwrap();
+
+        let migrated = load_presets(&root, &root).unwrap();
+        let expected = vec![format!(
+            "release ({}): foo[prod] bar[]",
+            PresetOrigin::Personal.label()
+        )];
+        assert_eq!(contents(&migrated), expected);
+        assert!(presets_path(&root, PresetOrigin::Personal).exists());
+        assert!(!presets_path(&root, PresetOrigin::Shared).exists());
+
+        // Once migrated, the personal file is read and the legacy file ignored.
+        fs::write(root.join(LEGACY_PRESETS_FILE), "[]").unwrap();
+        assert_eq!(contents(&load_presets(&root, &root).unwrap()), expected);
+
+        save_presets(&root, &migrated).unwrap();
+        assert_eq!(contents(&load_presets(&root, &root).unwrap()), expected);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn presets_from_a_newer_version_are_refused() {
+        let root = scratch_dir("future");
+        let path = presets_path(&root, PresetOrigin::Shared);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "version = 99\n[[presets]]\nname = \"x\"\n").unwrap();
+        assert!(matches!(
+            load_presets(&root, &root),
+            Err(PresetError::UnsupportedVersion(_, 99))
+        ));
+
+        fs::write(&path, "[[presets]]\nname = \"x\"\n").unwrap();
+        assert!(matches!(
+            load_presets(&root, &root),
+            Err(PresetError::Parse(..))
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn saving_leaves_unchanged_files_alone() {
+        let root = scratch_dir("unchanged");
+        let shared = presets_path(&root, PresetOrigin::Shared);
+        fs::create_dir_all(shared.parent().unwrap()).unwrap();
+        let text = "# Team presets\nversion = 1\n\n[[presets]]\nname = \"release\"\n";
+        fs::write(&shared, text).unwrap();
+
+        let mut presets = load_presets(&root, &root).unwrap();
+        presets.push(Preset {
+            name: "mine".to_string(),
+            origin: PresetOrigin::Personal,
+            ..Default::default()
+        });
+        save_presets(&root, &presets).unwrap();
+        assert_eq!(fs::read_to_string(&shared).unwrap(), text);
+        assert_eq!(
+            contents(&load_presets(&root, &root).unwrap()),
+            contents(&presets)
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn personal_presets_are_added_to_an_existing_gitignore() {
+        let dir = scratch_dir("gitignore");
+        fs::write(dir.join(".gitignore"), "cache/").unwrap();
+        ignore_personal_presets(&dir).unwrap();
+        ignore_personal_presets(&dir).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join(".gitignore")).unwrap(),
+            "cache/\npresets.local.toml\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::model::{Artifact, BuildEvent, BuildStatus, JobOutcome};
+
+/// Machine-readable summary of one build, written as JSON for release
+/// pipelines and dashboards.
+#[derive(Clone, Debug, Serialize)]
+pub struct BuildReport {
+    pub started_at: String,
+    pub finished_at: String,
+    pub success: bool,
+    pub cancelled: bool,
+    pub programs: Vec<ProgramReport>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProgramReport {
+    pub name: String,
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+    pub command: String,
+    pub cwd: PathBuf,
+    pub status: BuildStatus,
+    /// Set when the build command could not be started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub exit_code: Option<i32>,
+    pub duration_secs: f64,
+    pub artifacts: Vec<Artifact>,
+    pub idl: Vec<PathBuf>,
+}
+
+impl BuildReport {
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+}
+
+/// Folds a stream of [`BuildEvent`]s into a [`BuildReport`].
+#[derive(Default)]
+pub struct ReportBuilder {
+    started_at: Option<DateTime<Local>>,
+    programs: Vec<ProgramReport>,
+}
+
+impl ReportBuilder {
+    /// Records `event`, returning the finished report once the build ends.
+    pub fn record(&mut self, event: &BuildEvent) -> Option<BuildReport> {
+        let started_at = *self.started_at.get_or_insert_with(Local::now);
+
+        match event {
+            BuildEvent::JobStarted {
+                program,
+                features,
+                no_default_features,
+                command,
+                cwd,
+            } => self.programs.push(ProgramReport {
+                name: program.clone(),
+                features: features.clone(),
+                no_default_features: *no_default_features,
+                command: command.clone(),
+                cwd: cwd.clone(),
+                status: BuildStatus::Running,
+                error: None,
+                exit_code: None,
+                duration_secs: 0.0,
+                artifacts: Vec::new(),
+                idl: Vec::new(),
+            }),
+            BuildEvent::JobFinished {
+                program,
+                outcome,
+                exit_code,
+                duration,
+                artifacts,
+                idl,
+            } => {
+                if let Some(report) = self.programs.iter_mut().rev().find(|p| p.name == *program) {
+                    report.status = outcome.into();
+                    if let JobOutcome::Error(e) = outcome {
+                        report.error = Some(e.clone());
+                    }
+                    report.exit_code = *exit_code;
+                    report.duration_secs = duration.as_secs_f64();
+                    report.artifacts = artifacts.clone();
+                    report.idl = idl.clone();
+                }
+            }
+            BuildEvent::JobSkipped { program } => self.programs.push(ProgramReport {
+                name: program.clone(),
+                features: Vec::new(),
+                no_default_features: false,
+                command: String::new(),
+                cwd: PathBuf::new(),
+                status: BuildStatus::Skipped,
+                error: None,
+                exit_code: None,
+                duration_secs: 0.0,
+                artifacts: Vec::new(),
+                idl: Vec::new(),
+            }),
+            BuildEvent::Output { .. } => {}
+            BuildEvent::AllDone | BuildEvent::Cancelled => {
+                let cancelled = matches!(event, BuildEvent::Cancelled);
+                let programs = std::mem::take(&mut self.programs);
+                self.started_at = None;
+                return Some(BuildReport {
+                    started_at: started_at.to_rfc3339(),
+                    finished_at: Local::now().to_rfc3339(),
+                    success: !cancelled
+                        && programs.iter().all(|p| p.status == BuildStatus::Succeeded),
+                    cancelled,
+                    programs,
+                });
+            }
+        }
+        None
+    }
+}
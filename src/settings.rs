@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAX_RECENT_WORKSPACES: usize = 10;
+
+/// Per-user application state, stored as JSON in the user config directory.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Most recently opened workspace first.
+    pub recent_workspaces: Vec<PathBuf>,
+    /// Inner size of the main window when the app was last closed.
+    pub window_size: Option<[f32; 2]>,
+    pub workspaces: HashMap<PathBuf, WorkspaceSettings>,
+}
+
+/// UI state remembered separately for each workspace.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceSettings {
+    /// TypeScript IDL output directory.
+    pub build_dir: Option<String>,
+    pub selected_program: Option<String>,
+    /// Selected feature names, keyed by program name.
+    pub selected_features: HashMap<String, Vec<String>>,
+    /// Programs included in "Build"; `None` in settings written before
+    /// inclusion was separate from feature selection.
+    pub included_programs: Option<Vec<String>>,
+    /// Programs built with `--no-default-features`.
+    pub no_default_features: Vec<String>,
+}
+
+impl Settings {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("sbt").join("settings.json"))
+    }
+
+    /// Loads settings, falling back to defaults if the file is missing or unreadable.
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if let Ok(json) = serde_json::to_string_pretty(self) {
+            let _ = fs::write(path, json);
+        }
+    }
+
+    pub fn last_workspace(&self) -> Option<&Path> {
+        self.recent_workspaces.first().map(PathBuf::as_path)
+    }
+
+    /// Moves `root` to the front of the recent workspaces list.
+    pub fn add_recent_workspace(&mut self, root: &Path) {
+        self.recent_workspaces.retain(|p| p != root);
+        self.recent_workspaces.insert(0, root.to_path_buf());
+        self.recent_workspaces.truncate(MAX_RECENT_WORKSPACES);
+    }
+}
@@ -1,39 +1,69 @@
 use eframe::egui;
 use std::process::Command;
-use std::thread;
 
-use crate::build::{build_all, run_build};
-use crate::model::{BuildTool, Preset};
+use crate::build::ScanError;
+use crate::model::{
+    BuildJob, BuildStatus, BuildTool, Feature, FeatureRef, Framework, Preset, PresetEditor,
+    PresetOrigin, PresetProgram, Program, ProgramBuildStatus,
+};
+use crate::plan::BuildPlan;
+use crate::presets::{
+    apply_preset, preset_job, preset_violations, validate_preset, PresetError, PresetRepair,
+};
 use rfd::FileDialog;
 
+/// Changes to the preset list requested from the presets bar.
+enum PresetAction {
+    Remove,
+    Promote,
+    CopyToPersonal,
+    Duplicate,
+    /// Drop the dragged preset at the given position.
+    MoveTo(usize),
+    Edit,
+    Repair,
+    /// Select the preset's programs and features and build them.
+    Run,
+    /// Show the commands building the preset would run.
+    Preview,
+}
+
 pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::Frame) {
     egui::CentralPanel::default().show(ctx, |ui| {
         ui.horizontal(|ui| {
             ui.heading("Solana Program Build Tool");
             ui.add_space(10.0);
-            if ui.button("Refresh").clicked() {
-                let old_programs = app.programs.clone();
-                app.programs = crate::build::scan_programs();
-                for new_program in &mut app.programs {
-                    if let Some(old_program) =
-                        old_programs.iter().find(|p| p.name == new_program.name)
-                    {
-                        new_program.selected = new_program
-                            .features
-                            .iter()
-                            .map(|f| {
-                                old_program
-                                    .features
-                                    .iter()
-                                    .position(|of| of.name == f.name)
-                                    .map(|i| old_program.selected.get(i).copied().unwrap_or(false))
-                                    .unwrap_or(false)
-                            })
-                            .collect();
-                    }
+            if ui
+                .button("Refresh")
+                .on_hover_text("Rescan programs and reload presets and config")
+                .clicked()
+            {
+                app.open_workspace(app.workspace_root.clone());
+            }
+            if ui.button("Open Workspace…").clicked() {
+                if let Some(path) = FileDialog::new()
+                    .set_directory(&app.workspace_root)
+                    .set_title("Select Workspace Directory")
+                    .pick_folder()
+                {
+                    app.open_workspace(path);
                 }
-                app.selected_program = None;
             }
+            let recent = app.settings.recent_workspaces.clone();
+            ui.add_enabled_ui(!recent.is_empty(), |ui| {
+                ui.menu_button("Recent", |ui| {
+                    for path in recent {
+                        let current = path == app.workspace_root;
+                        if ui
+                            .add_enabled(!current, egui::Button::new(path.display().to_string()))
+                            .clicked()
+                        {
+                            app.open_workspace(path);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
             ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
                 let options_id = egui::Id::new("options_window");
                 if ui.button("Options").clicked() {
@@ -54,7 +84,7 @@ pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::
                             ui.label(current_dir_str);
                             if ui.button("Browse...").clicked() {
                                 if let Some(path) = FileDialog::new()
-                                    .set_directory(std::env::current_dir().unwrap_or_default())
+                                    .set_directory(&app.workspace_root)
                                     .set_title("Select TypeScript IDL Output Directory")
                                     .pick_folder()
                                 {
@@ -76,8 +106,28 @@ pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::
                 }
             });
         });
+        ui.label(
+            egui::RichText::new(format!("Workspace: {}", app.workspace_root.display()))
+                .small()
+                .weak(),
+        );
         ui.add_space(5.0);
 
+        if app.scan_error.is_some() {
+            render_scan_error(app, ui);
+            ui.add_space(5.0);
+        }
+        if let Some(error) = &app.config_error {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!(
+                    "Ignoring invalid workspace config, using default profiles. {}",
+                    error
+                ),
+            );
+            ui.add_space(5.0);
+        }
+
         ui.columns(2, |columns| {
             let pane_height: f32 = 250.0;
 
@@ -87,42 +137,81 @@ pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::
                     .id_salt("program_list")
                     .max_height(pane_height.max(200.0))
                     .show(ui, |ui| {
-                        for (i, program) in app.programs.iter().enumerate() {
-                            if ui
-                                .selectable_label(app.selected_program == Some(i), &program.name)
-                                .clicked()
-                            {
-                                app.selected_program = Some(i);
-                            }
+                        if app.programs.is_empty() && app.scan_error.is_none() {
+                            ui.label(format!(
+                                "No programs found in {}.",
+                                app.workspace_root.display()
+                            ));
+                            ui.label(
+                                egui::RichText::new(
+                                    "Programs are workspace packages under this directory \
+                                     that depend on anchor-lang, or cdylib packages that \
+                                     depend on solana-program or pinocchio. Open the \
+                                     workspace root or click Refresh after adding one.",
+                                )
+                                .small()
+                                .weak(),
+                            );
+                        }
+                        for (i, program) in app.programs.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut program.included, "")
+                                    .on_hover_text("Include in Build");
+                                if ui
+                                    .selectable_label(
+                                        app.selected_program == Some(i),
+                                        &program.name,
+                                    )
+                                    .clicked()
+                                {
+                                    app.selected_program = Some(i);
+                                }
+                                if program.framework != Framework::Anchor {
+                                    ui.label(
+                                        egui::RichText::new(program.framework.label())
+                                            .small()
+                                            .weak(),
+                                    )
+                                    .on_hover_text("Built with cargo build-sbf");
+                                }
+                                if let Some(status) = app.build_status.get(&program.name) {
+                                    status_badge(ui, status);
+                                }
+                            });
                         }
                     });
             });
 
             columns[1].group(|ui| {
-                ui.label("Features:");
-                if let Some(selected_idx) = app.selected_program {
+                let all_programs_id = egui::Id::new("features_all_programs");
+                let mut all_programs =
+                    ui.data_mut(|data| data.get_temp::<bool>(all_programs_id).unwrap_or(false));
+                ui.horizontal(|ui| {
+                    ui.label("Features:");
+                    let selected = ui.selectable_value(&mut all_programs, false, "Selected Program");
+                    let all = ui
+                        .selectable_value(&mut all_programs, true, "All Programs")
+                        .on_hover_text("Toggle a feature in every program that defines it");
+                    if selected.changed() || all.changed() {
+                        ui.data_mut(|data| data.insert_temp(all_programs_id, all_programs));
+                    }
+                });
+                if all_programs {
+                    egui::ScrollArea::vertical()
+                        .id_salt("workspace_feature_list")
+                        .max_height(pane_height.max(200.0))
+                        .show(ui, |ui| render_workspace_features(ui, &mut app.programs));
+                } else if let Some(selected_idx) = app.selected_program {
                     let program = &mut app.programs[selected_idx];
                     if program.selected.len() != program.features.len() {
                         program.selected.resize(program.features.len(), false);
                     }
+                    ui.checkbox(&mut program.no_default_features, "No default features")
+                        .on_hover_text("Build with --no-default-features");
                     egui::ScrollArea::vertical()
                         .id_salt("feature_list")
                         .max_height(pane_height.max(200.0))
-                        .show(ui, |ui| {
-                            for (i, feature) in program.features.iter().enumerate() {
-                                let mut label = egui::RichText::new(&feature.name);
-                                if !feature.sub_features.is_empty() {
-                                    label = label.underline();
-                                }
-                                let checkbox = ui.checkbox(&mut program.selected[i], label);
-                                if !feature.sub_features.is_empty() {
-                                    checkbox.on_hover_text(format!(
-                                        "Includes:\n{}",
-                                        feature.sub_features.join("\n")
-                                    ));
-                                }
-                            }
-                        });
+                        .show(ui, |ui| render_feature_list(ui, program));
                 } else {
                     ui.label("Select a program to view features.");
                 }
@@ -133,9 +222,12 @@ pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::
 
         ui.horizontal(|ui| {
             ui.label("Presets:");
+            if let Some(error) = &app.preset_error {
+                ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+            }
         });
 
-        let mut preset_to_remove: Option<usize> = None;
+        let mut preset_action: Option<(usize, PresetAction)> = None;
 
         // Use ScrollArea::horizontal for scrollable presets rather than wrapping
         egui::ScrollArea::horizontal()
@@ -144,120 +236,345 @@ pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::
                 ui.horizontal(|ui| {
                     for (i, preset) in app.presets.iter().enumerate() {
                         ui.add_space(4.0);
-                        ui.group(|ui| {
+                        let group = ui.group(|ui| {
                             ui.horizontal(|ui| {
-                        let button = ui.button(&preset.name);
-                        let clicked = button.clicked();
-                        let details = preset
-                            .programs
-                            .iter()
-                            .map(|(prog, feats)| {
-                                if feats.is_empty() {
-                                    prog.clone()
+                                ui.dnd_drag_source(egui::Id::new(("preset_drag", i)), i, |ui| {
+                                    ui.label("⠿");
+                                })
+                                .response
+                                .on_hover_text("Drag to reorder among the presets from the same file");
+                                let issues = app.preset_issues.get(i).filter(|i| !i.is_empty());
+                                let mut label = egui::RichText::new(format!(
+                                    "{} {}",
+                                    preset.origin.icon(),
+                                    preset.name
+                                ));
+                                if issues.is_some() {
+                                    label = label.color(ui.visuals().warn_fg_color);
+                                }
+                                let button = ui.button(label);
+                                let clicked = button.clicked();
+                                let details = preset
+                                    .programs
+                                    .iter()
+                                    .map(|entry| {
+                                        let mut line = if entry.features.is_empty() {
+                                            entry.name.clone()
+                                        } else {
+                                            format!("{}: {}", entry.name, entry.features.join(", "))
+                                        };
+                                        if entry.no_default_features {
+                                            line.push_str(" (no default features)");
+                                        }
+                                        line
+                                    })
+                                    .collect::<Vec<String>>()
+                                    .join("\n");
+                                let mut hover = format!(
+                                    "{} preset\nContains:\n{}",
+                                    preset.origin.label(),
+                                    details
+                                );
+                                if let Some(profile) = &preset.profile {
+                                    hover.push_str(&format!("\nProfile: {}", profile));
+                                }
+                                if let Some(dir) = &preset.build_dir {
+                                    hover.push_str(&format!("\nIDL directory: {}", dir));
+                                }
+                                let extra_args = preset
+                                    .anchor_args
+                                    .iter()
+                                    .chain(&preset.cargo_args)
+                                    .cloned()
+                                    .collect::<Vec<_>>();
+                                if !extra_args.is_empty() {
+                                    hover.push_str(&format!(
+                                        "\nExtra args: {}",
+                                        extra_args.join(" ")
+                                    ));
+                                }
+                                for (key, value) in &preset.env {
+                                    hover.push_str(&format!("\n{}={}", key, value));
+                                }
+                                hover.push_str(if preset.run_on_click {
+                                    "\n\nClick to select and build"
                                 } else {
-                                    format!("{}: {}", prog, feats.join(", "))
+                                    "\n\nClick to select"
+                                });
+                                button.on_hover_text(hover);
+                                if clicked {
+                                    if preset.run_on_click {
+                                        preset_action = Some((i, PresetAction::Run));
+                                    } else {
+                                        apply_preset(&mut app.programs, preset);
+                                    }
                                 }
-                            })
-                            .collect::<Vec<String>>()
-                            .join("\n");
-                        button.on_hover_text(format!("Contains:\n{}", details));
-                        if clicked {
-                            for (prog_name, features) in &preset.programs {
-                                if let Some(program) =
-                                    app.programs.iter_mut().find(|p| p.name == *prog_name)
+                                if !preset.run_on_click
+                                    && ui
+                                        .small_button("▶")
+                                        .on_hover_text("Select and build")
+                                        .clicked()
                                 {
-                                    program.selected.clear();
-                                    program.selected.resize(program.features.len(), false);
-                                    for feature in features {
-                                        if let Some(idx) =
-                                            program.features.iter().position(|f| f.name == *feature)
+                                    preset_action = Some((i, PresetAction::Run));
+                                }
+                                if ui
+                                    .small_button("🔍")
+                                    .on_hover_text("Show the commands this preset runs")
+                                    .clicked()
+                                {
+                                    preset_action = Some((i, PresetAction::Preview));
+                                }
+
+                                if let Some(issues) = issues {
+                                    let warning = egui::RichText::new("⚠")
+                                        .color(ui.visuals().warn_fg_color);
+                                    if ui
+                                        .small_button(warning)
+                                        .on_hover_text(format!(
+                                            "Out of date with this workspace:\n{}\nClick to prune or remap.",
+                                            issues.describe().join("\n")
+                                        ))
+                                        .clicked()
+                                    {
+                                        preset_action = Some((i, PresetAction::Repair));
+                                    }
+                                }
+
+                                match preset.origin {
+                                    PresetOrigin::Personal => {
+                                        if ui
+                                            .small_button("⬆")
+                                            .on_hover_text(
+                                                "Promote to a shared preset (.sbt/presets.toml)",
+                                            )
+                                            .clicked()
                                         {
-                                            program.selected[idx] = true;
+                                            preset_action = Some((i, PresetAction::Promote));
+                                        }
+                                    }
+                                    PresetOrigin::Shared => {
+                                        if ui
+                                            .small_button("⎘")
+                                            .on_hover_text("Copy to your personal presets")
+                                            .clicked()
+                                        {
+                                            preset_action = Some((i, PresetAction::CopyToPersonal));
                                         }
                                     }
                                 }
-                            }
-                        }
 
-                        let delete_confirmation_id =
-                            egui::Id::new(format!("delete_confirmation_{}", i));
-                        if ui.small_button("🗑").on_hover_text("Delete Preset").clicked() {
-                            ctx.memory_mut(|mem| mem.data.insert_temp(delete_confirmation_id, true));
-                        }
+                                if ui.small_button("✏").on_hover_text("Edit Preset").clicked() {
+                                    preset_action = Some((i, PresetAction::Edit));
+                                }
+                                if ui
+                                    .small_button("⧉")
+                                    .on_hover_text("Duplicate Preset")
+                                    .clicked()
+                                {
+                                    preset_action = Some((i, PresetAction::Duplicate));
+                                }
 
-                        let mut show_delete_confirmation = ctx
-                            .memory(|mem| mem.data.get_temp(delete_confirmation_id).unwrap_or(false));
-                        if show_delete_confirmation {
-                            egui::Window::new("Confirm Delete")
-                                .collapsible(false)
-                                .resizable(false)
-                                .show(ctx, |ui| {
-                                    ui.label(format!(
-                                        "Are you sure you want to delete preset '{}'?",
-                                        preset.name
-                                    ));
-                                    ui.horizontal(|ui| {
-                                        if ui.button("Yes").clicked() {
-                                            preset_to_remove = Some(i);
-                                            show_delete_confirmation = false;
-                                            ctx.memory_mut(|mem| {
-                                                mem.data.remove::<bool>(delete_confirmation_id)
-                                            });
-                                        }
-                                        ui.add_space(10.0);
-                                        if ui.button("No").clicked() {
-                                            show_delete_confirmation = false;
-                                            ctx.memory_mut(|mem| {
-                                                mem.data.remove::<bool>(delete_confirmation_id)
-                                            });
-                                        }
+                                let delete_confirmation_id =
+                                    egui::Id::new(format!("delete_confirmation_{}", i));
+                                if ui
+                                    .small_button("🗑")
+                                    .on_hover_text("Delete Preset")
+                                    .clicked()
+                                {
+                                    ctx.memory_mut(|mem| {
+                                        mem.data.insert_temp(delete_confirmation_id, true)
                                     });
+                                }
+
+                                let mut show_delete_confirmation = ctx.memory(|mem| {
+                                    mem.data.get_temp(delete_confirmation_id).unwrap_or(false)
                                 });
-                        }
-                    });
+                                if show_delete_confirmation {
+                                    egui::Window::new("Confirm Delete")
+                                        .collapsible(false)
+                                        .resizable(false)
+                                        .show(ctx, |ui| {
+                                            ui.label(format!(
+                                                "Are you sure you want to delete preset '{}'?",
+                                                preset.name
+                                            ));
+                                            ui.horizontal(|ui| {
+                                                if ui.button("Yes").clicked() {
+                                                    preset_action = Some((i, PresetAction::Remove));
+                                                    show_delete_confirmation = false;
+                                                    ctx.memory_mut(|mem| {
+                                                        mem.data
+                                                            .remove::<bool>(delete_confirmation_id)
+                                                    });
+                                                }
+                                                ui.add_space(10.0);
+                                                if ui.button("No").clicked() {
+                                                    show_delete_confirmation = false;
+                                                    ctx.memory_mut(|mem| {
+                                                        mem.data
+                                                            .remove::<bool>(delete_confirmation_id)
+                                                    });
+                                                }
+                                            });
+                                        });
+                                }
+                            });
                         });
+                        // Presets load shared first, then personal, so they
+                        // can only be reordered within their own file.
+                        if let Some(from) = group.response.dnd_release_payload::<usize>() {
+                            if *from != i && app.presets[*from].origin == preset.origin {
+                                preset_action = Some((*from, PresetAction::MoveTo(i)));
+                            }
+                        }
                     }
                 });
             });
 
-        if let Some(idx) = preset_to_remove {
-            app.presets.remove(idx);
-            if let Ok(json) = serde_json::to_string_pretty(&app.presets) {
-                let _ = std::fs::write("presets.json", json);
+        if let Some((idx, action)) = preset_action {
+            match action {
+                PresetAction::Remove => {
+                    app.remove_preset(idx);
+                }
+                PresetAction::Promote => app.promote_preset(idx),
+                PresetAction::CopyToPersonal => {
+                    let mut copy = app.presets[idx].clone();
+                    copy.origin = PresetOrigin::Personal;
+                    app.insert_preset(copy);
+                }
+                PresetAction::Duplicate => {
+                    let mut copy = app.presets[idx].clone();
+                    copy.name = format!("{} (copy)", copy.name);
+                    app.insert_preset_at(idx + 1, copy);
+                }
+                PresetAction::MoveTo(to) => app.move_preset(idx, to),
+                PresetAction::Edit => {
+                    app.preset_editor = Some(PresetEditor::new(idx, &app.presets[idx]));
+                }
+                PresetAction::Run => {
+                    let preset = &app.presets[idx];
+                    apply_preset(&mut app.programs, preset);
+                    match preset_job(&app.programs, preset, &app.config, app.build_dir.clone()) {
+                        Ok(job) => app.enqueue_build(job),
+                        Err(e) => app.build_output.push_str(&format!("Error: {}\n", e)),
+                    }
+                }
+                PresetAction::Preview => {
+                    match preset_job(
+                        &app.programs,
+                        &app.presets[idx],
+                        &app.config,
+                        app.build_dir.clone(),
+                    ) {
+                        Ok(job) => app.plan_preview = Some(job),
+                        Err(e) => app.build_output.push_str(&format!("Error: {}\n", e)),
+                    }
+                }
+                PresetAction::Repair => {
+                    let issues = validate_preset(&app.presets[idx], &app.programs);
+                    if !issues.is_empty() {
+                        app.preset_repair = Some((idx, PresetRepair::prune(&issues)));
+                    }
+                }
+            }
+            if !matches!(
+                action,
+                PresetAction::Edit
+                    | PresetAction::Repair
+                    | PresetAction::Run
+                    | PresetAction::Preview
+            ) {
+                app.save_presets();
             }
         }
 
+        render_preset_editor(app, ctx);
+        render_preset_repair(app, ctx);
+        render_plan_preview(app, ctx);
+
         ui.add_space(5.0);
 
         ui.horizontal(|ui| {
-            if ui.button("Build").clicked() {
-                app.build_output.clear();
-                let tx = app.build_tx.clone();
-                let programs = app.programs.clone();
-                let build_dir = app.build_dir.clone();
-                thread::spawn(move || {
-                    run_build(programs, tx, build_dir);
+            let busy = app.build_state.is_busy();
+            let queue_hint = |response: egui::Response| {
+                if busy {
+                    response.on_hover_text("A build is already running; this one will be queued.")
+                } else {
+                    response
+                }
+            };
+            let mut requested = None;
+            if queue_hint(ui.button("Build")).clicked() {
+                requested = Some(BuildJob::Selected {
+                    programs: app.programs.clone(),
+                    build_dir: app.build_dir.clone(),
                 });
             }
-            if ui.button("Build All (Prod)").clicked() {
-                app.build_output.clear();
-                let tx = app.build_tx.clone();
-                let programs = app.programs.clone();
-                let build_dir = app.build_dir.clone();
-                thread::spawn(move || {
-                    build_all(programs, tx, true, build_dir);
+            let mut profile_requested = None;
+            for profile in &app.config.profiles {
+                let label = format!("Build All ({})", profile.name);
+                let mut hover = if profile.features.is_empty() {
+                    "Build every program with its default features".to_string()
+                } else {
+                    format!("Build every program with: {}", profile.features.join(", "))
+                };
+                let extra_args = profile
+                    .anchor_args
+                    .iter()
+                    .chain(&profile.cargo_args)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !extra_args.is_empty() {
+                    hover.push_str(&format!("\nExtra args: {}", extra_args.join(" ")));
+                }
+                let button = ui.button(label).on_hover_ui(|ui| {
+                    ui.label(&hover);
+                    let plan = BuildPlan::new(&BuildJob::All {
+                        programs: app.programs.clone(),
+                        profile: profile.clone(),
+                        build_dir: app.build_dir.clone(),
+                    });
+                    ui.add(egui::Label::new(
+                        egui::RichText::new(plan.shell_script()).monospace(),
+                    ));
                 });
+                if queue_hint(button).clicked() {
+                    profile_requested = Some(profile.clone());
+                }
             }
-            if ui.button("Build All (Default)").clicked() {
-                app.build_output.clear();
-                let tx = app.build_tx.clone();
-                let programs = app.programs.clone();
-                let build_dir = app.build_dir.clone();
-                thread::spawn(move || {
-                    build_all(programs, tx, false, build_dir);
-                });
+            if let Some(profile) = profile_requested {
+                app.request_profile_build(&profile);
+            }
+            if let Some(job) = requested {
+                app.enqueue_build(job);
             }
-            if ui.button("Save Preset").clicked() {
+
+            render_profile_warning(app, ctx);
+            render_blocked_build(app, ctx);
+
+            let cancellable = app
+                .build_state
+                .running
+                .as_ref()
+                .is_some_and(|running| !running.cancel.is_cancelled());
+            if ui
+                .add_enabled(cancellable, egui::Button::new("Cancel"))
+                .on_hover_text("Stop the running build and skip remaining programs")
+                .clicked()
+            {
+                if let Some(running) = &app.build_state.running {
+                    running.cancel.cancel();
+                }
+            }
+            let presets_loaded = !app
+                .preset_error
+                .as_ref()
+                .is_some_and(PresetError::is_load_error);
+            if ui
+                .add_enabled(presets_loaded, egui::Button::new("Save Preset"))
+                .on_disabled_hover_text("Fix the presets file and click Refresh first")
+                .clicked()
+            {
                 let preset_popup_id = egui::Id::new("preset_popup_window");
                 ctx.memory_mut(|mem| mem.data.insert_temp(preset_popup_id, true));
             }
@@ -271,8 +588,14 @@ pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::
             if show_preset_popup {
                 let preset_name_id = egui::Id::new("preset_name_input");
 
+                let preset_origin_id = egui::Id::new("preset_origin_input");
+
                 let mut preset_name = ctx
                     .data_mut(|data| data.get_temp::<String>(preset_name_id).unwrap_or_default());
+                let mut preset_origin = ctx.data_mut(|data| {
+                    data.get_temp::<PresetOrigin>(preset_origin_id)
+                        .unwrap_or(PresetOrigin::Personal)
+                });
 
                 egui::Window::new("Save Preset")
                     .collapsible(false)
@@ -288,11 +611,55 @@ pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::
                                 });
                             }
                         });
-                        if ui.button("Save").clicked() && !preset_name.is_empty() {
-                            let preset_programs: Vec<(String, Vec<String>)> = app
+                        ui.horizontal(|ui| {
+                            ui.label("Save to:");
+                            let personal = ui
+                                .radio_value(
+                                    &mut preset_origin,
+                                    PresetOrigin::Personal,
+                                    PresetOrigin::Personal.label(),
+                                )
+                                .on_hover_text("Only for you (.sbt/presets.local.toml)");
+                            let shared = ui
+                                .radio_value(
+                                    &mut preset_origin,
+                                    PresetOrigin::Shared,
+                                    PresetOrigin::Shared.label(),
+                                )
+                                .on_hover_text("For the whole team (.sbt/presets.toml)");
+                            if personal.changed() || shared.changed() {
+                                ctx.data_mut(|data| {
+                                    data.insert_temp(preset_origin_id, preset_origin);
+                                });
+                            }
+                        });
+                        let violations: Vec<String> = app
+                            .programs
+                            .iter()
+                            .filter(|p| p.included)
+                            .flat_map(|p| p.group_violations(&[]))
+                            .collect();
+                        for violation in &violations {
+                            ui.colored_label(ui.visuals().error_fg_color, violation);
+                        }
+                        let disabled_reason = if preset_name.is_empty() {
+                            Some("Enter a name for the preset")
+                        } else if !app.programs.iter().any(|p| p.included) {
+                            Some("Include at least one program")
+                        } else if !violations.is_empty() {
+                            Some("Fix the conflicting features first")
+                        } else {
+                            None
+                        };
+                        if ui
+                            .add_enabled(disabled_reason.is_none(), egui::Button::new("Save"))
+                            .on_disabled_hover_text(disabled_reason.unwrap_or_default())
+                            .clicked()
+                        {
+                            let preset_programs: Vec<PresetProgram> = app
                                 .programs
                                 .iter()
-                                .filter(|p| !p.selected.iter().all(|&s| !s)) // At least one feature selected
+                                .filter(|p| p.included)
                                 .map(|p| {
                                     let selected_features = p
                                         .features
@@ -301,27 +668,23 @@ pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::
                                         .filter(|(_, &sel)| sel)
                                         .map(|(f, _)| f.name.clone())
                                         .collect::<Vec<String>>();
-                                    (p.name.clone(), selected_features)
+                                    PresetProgram {
+                                        name: p.name.clone(),
+                                        features: selected_features,
+                                        no_default_features: p.no_default_features,
+                                    }
                                 })
                                 .collect();
-                            if !preset_programs.is_empty() {
-                                app.presets.push(Preset {
-                                    name: preset_name.clone(),
-                                    programs: preset_programs.clone(),
-                                });
-                                println!(
-                                    "Saved preset: {} with {:?}",
-                                    preset_name, preset_programs
-                                ); // Debug
-                                if let Ok(json) = serde_json::to_string_pretty(&app.presets) {
-                                    let _ = std::fs::write("presets.json", json);
-                                }
-                            } else {
-                                println!(
-                                    "No features selected to save for preset: {}",
-                                    preset_name
-                                ); // Debug
-                            }
+                            app.insert_preset(Preset {
+                                name: preset_name.clone(),
+                                origin: preset_origin,
+                                // Capture the IDL directory so the preset
+                                // doesn't depend on later Options changes.
+                                build_dir: app.build_dir.clone(),
+                                programs: preset_programs,
+                                ..Default::default()
+                            });
+                            app.save_presets();
 
                             show_preset_popup = false;
                             ctx.data_mut(|data| {
@@ -344,53 +707,70 @@ pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::
             }
         });
 
+        if app.build_state.is_busy() || !app.build_state.queue.is_empty() {
+            ui.add_space(5.0);
+            render_build_queue(app, ui);
+        }
         ui.add_space(5.0);
 
         ui.group(|ui| {
+            // The same plan the executor runs, so the commands shown are exact.
+            let plan = BuildPlan::new(&BuildJob::Selected {
+                programs: app.programs.clone(),
+                build_dir: app.build_dir.clone(),
+            });
             ui.horizontal(|ui| {
                 ui.label("Build Preview:");
-                let build_preview = app
-                    .programs
-                    .iter()
-                    .filter(|p| !p.selected.iter().all(|&s| !s))
-                    .map(|p| {
-                        let selected_features = p
-                            .features
-                            .iter()
-                            .zip(&p.selected)
-                            .filter(|(_, &sel)| sel)
-                            .map(|(f, _)| f.name.clone())
-                            .collect::<Vec<String>>();
-                        format!("{}: {}", p.name, selected_features.join(", "))
-                    })
-                    .collect::<Vec<String>>()
-                    .join("\n");
-                if build_preview.is_empty() {
-                    egui::ScrollArea::vertical()
-                        .id_salt("build_preview")
-                        .max_height(150.0)
-                        .auto_shrink([false, false])
-                        .stick_to_bottom(true)
-                        .show(ui, |ui| {
-                            ui.label("No programs selected for build.");
-                        });
-                } else {
-                    egui::ScrollArea::vertical()
-                        .id_salt("build_preview")
-                        .max_height(150.0)
-                        .auto_shrink([false, false])
-                        .stick_to_bottom(true)
-                        .show(ui, |ui| {
-                            ui.label(build_preview);
-                        });
+                if ui
+                    .add_enabled(!plan.steps.is_empty(), egui::Button::new("Copy Commands"))
+                    .on_hover_text("Copy the build commands as a shell script")
+                    .clicked()
+                {
+                    ui.ctx().copy_text(plan.shell_script());
                 }
             });
+            egui::ScrollArea::vertical()
+                .id_salt("build_preview")
+                .max_height(150.0)
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    if plan.steps.is_empty() {
+                        ui.label("No programs selected for build.");
+                    }
+                    render_plan(ui, &plan);
+                });
         });
 
         ui.add_space(5.0);
 
         ui.group(|ui| {
-            ui.label("Build Output:");
+            ui.horizontal(|ui| {
+                ui.label("Build Output:");
+                if let Some(report) = &app.last_report {
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui
+                            .small_button("Save Report…")
+                            .on_hover_text("Save a JSON report of the last build")
+                            .clicked()
+                        {
+                            if let Some(path) = FileDialog::new()
+                                .set_title("Save Build Report")
+                                .set_file_name("build-report.json")
+                                .add_filter("JSON", &["json"])
+                                .save_file()
+                            {
+                                if let Err(e) = report.write(&path) {
+                                    app.build_output.push_str(&format!(
+                                        "Failed to write report to {}: {}\n",
+                                        path.display(),
+                                        e
+                                    ));
+                                }
+                            }
+                        }
+                    });
+                }
+            });
             egui::ScrollArea::vertical()
                 .id_salt("build_output")
                 .max_height(170.0)
@@ -428,3 +808,756 @@ pub fn render_ui(app: &mut BuildTool, ctx: &egui::Context, _frame: &mut eframe::
         });
     });
 }
+
+/// The selected program's features as an implication tree. Features turned
+/// on by a checked feature are shown checked and locked.
+fn render_feature_list(ui: &mut egui::Ui, program: &mut Program) {
+    let defaults = program.default_features();
+    let resolved = program.resolve_features(&program.selected_features());
+
+    // Exclusive groups are picked with radio buttons instead of checkboxes.
+    let grouped: Vec<String> = program
+        .feature_groups
+        .iter()
+        .flat_map(|g| g.features.iter().cloned())
+        .collect();
+    for group in program.feature_groups.clone() {
+        let members: Vec<usize> = group
+            .features
+            .iter()
+            .filter_map(|name| program.features.iter().position(|f| f.name == *name))
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.label(format!("{}:", group.name))
+                .on_hover_text("At most one of these can be enabled");
+            let before = members.iter().copied().find(|&i| program.selected[i]);
+            let mut current = before;
+            ui.radio_value(&mut current, None, "none");
+            for &i in &members {
+                let name = &program.features[i].name;
+                let label = if defaults.contains(name) {
+                    format!("{} (default)", name)
+                } else {
+                    name.clone()
+                };
+                let radio = ui.radio_value(&mut current, Some(i), label);
+                if let Some(description) = &program.features[i].description {
+                    radio.on_hover_text(description);
+                }
+            }
+            if current != before {
+                match current {
+                    Some(i) => {
+                        let name = program.features[i].name.clone();
+                        program.set_feature(&name, true);
+                    }
+                    None => {
+                        for &i in &members {
+                            program.selected[i] = false;
+                        }
+                    }
+                }
+            }
+        });
+    }
+    for violation in program.group_violations(&[]) {
+        ui.colored_label(ui.visuals().warn_fg_color, violation);
+    }
+
+    for i in 0..program.features.len() {
+        let feature = program.features[i].clone();
+        if grouped.contains(&feature.name) {
+            continue;
+        }
+        let enabled_by = resolved.enabler_of(&feature.name).map(str::to_string);
+        let row = |ui: &mut egui::Ui, program: &mut Program| {
+            ui.horizontal(|ui| {
+                match &enabled_by {
+                    Some(parent) if !program.selected[i] => {
+                        let mut hover = format!("Enabled by {}", parent);
+                        if let Some(description) = &feature.description {
+                            hover = format!("{}\n\n{}", description, hover);
+                        }
+                        ui.add_enabled(false, egui::Checkbox::new(&mut true, &feature.name))
+                            .on_disabled_hover_text(hover);
+                    }
+                    _ => {
+                        let checkbox = ui.checkbox(&mut program.selected[i], &feature.name);
+                        // Picking a feature implies building the program.
+                        if checkbox.changed() && program.selected[i] {
+                            program.included = true;
+                        }
+                        if let Some(description) = &feature.description {
+                            checkbox.on_hover_text(description);
+                        }
+                    }
+                }
+                if defaults.contains(&feature.name) {
+                    ui.weak("on by default").on_hover_text(
+                        "Enabled through the `default` feature; \
+                         turn on No default features to disable it",
+                    );
+                }
+                if let Some(description) = &feature.description {
+                    // The first line fits inline; the tooltip has the rest.
+                    let summary = description.lines().next().unwrap_or_default();
+                    ui.add(
+                        egui::Label::new(egui::RichText::new(summary).small().weak()).truncate(),
+                    )
+                    .on_hover_text(description);
+                }
+            });
+        };
+        if feature.sub_features.is_empty() {
+            row(ui, program);
+        } else {
+            let id = ui.make_persistent_id(("feature_tree", &program.name, &feature.name));
+            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                .show_header(ui, |ui| row(ui, program))
+                .body(|ui| {
+                    render_feature_tree(ui, program, &feature, &mut vec![feature.name.clone()])
+                });
+        }
+    }
+
+    let mut enabled = program.selected_features();
+    enabled.extend(defaults);
+    let dependencies = program.resolve_features(&enabled).dependencies;
+    if !dependencies.is_empty() {
+        ui.separator();
+        ui.label("Turns on in dependencies:");
+        for dependency in dependencies {
+            ui.weak(dependency.describe());
+        }
+    }
+}
+
+/// The union of every program's features. Each can be toggled for all the
+/// programs defining it at once, or expanded to toggle it per program.
+fn render_workspace_features(ui: &mut egui::Ui, programs: &mut [Program]) {
+    let mut names: Vec<String> = Vec::new();
+    for feature in programs.iter().flat_map(|p| &p.features) {
+        if feature.name != "default" && !names.contains(&feature.name) {
+            names.push(feature.name.clone());
+        }
+    }
+    names.sort();
+    if names.is_empty() {
+        ui.label("No program defines any features.");
+    }
+
+    for name in names {
+        let defining: Vec<usize> = (0..programs.len())
+            .filter(|&i| programs[i].features.iter().any(|f| f.name == name))
+            .collect();
+        let on = defining
+            .iter()
+            .filter(|&&i| programs[i].selected_features().contains(&name))
+            .count();
+        let description = defining.iter().find_map(|&i| {
+            let feature = programs[i].features.iter().find(|f| f.name == name)?;
+            feature.description.clone()
+        });
+
+        let id = ui.make_persistent_id(("workspace_feature", &name));
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+            .show_header(ui, |ui| {
+                let mut all_on = on == defining.len();
+                let partly_on = on > 0 && !all_on;
+                let checkbox =
+                    ui.add(egui::Checkbox::new(&mut all_on, &name).indeterminate(partly_on));
+                if checkbox.clicked() {
+                    // A partly enabled feature is switched on everywhere first.
+                    let enable = on < defining.len();
+                    for &i in &defining {
+                        programs[i].set_feature(&name, enable);
+                    }
+                }
+                if let Some(description) = &description {
+                    checkbox.on_hover_text(description);
+                }
+                let names = defining
+                    .iter()
+                    .map(|&i| programs[i].name.as_str())
+                    .collect::<Vec<_>>();
+                ui.weak(format!("{}/{} programs", on, defining.len()))
+                    .on_hover_text(format!("Defined by:\n{}", names.join("\n")));
+            })
+            .body(|ui| {
+                for &i in &defining {
+                    let mut enabled = programs[i].selected_features().contains(&name);
+                    if ui.checkbox(&mut enabled, &programs[i].name).changed() {
+                        programs[i].set_feature(&name, enabled);
+                    }
+                }
+            });
+    }
+}
+
+/// Lists what `feature` enables, expanding local features it turns on.
+fn render_feature_tree(
+    ui: &mut egui::Ui,
+    program: &Program,
+    feature: &Feature,
+    visited: &mut Vec<String>,
+) {
+    for implied in feature.implies() {
+        ui.label(egui::RichText::new(format!("↳ {}", implied.describe())).weak());
+        let FeatureRef::Local(name) = implied else {
+            continue;
+        };
+        if visited.contains(&name) {
+            continue;
+        }
+        if let Some(sub) = program.features.iter().find(|f| f.name == name) {
+            visited.push(name);
+            ui.indent(("feature_tree", &sub.name), |ui| {
+                render_feature_tree(ui, program, sub, visited)
+            });
+        }
+    }
+}
+
+fn render_profile_warning(app: &mut BuildTool, ctx: &egui::Context) {
+    let Some((job, missing)) = &app.pending_profile_build else {
+        return;
+    };
+    let mut confirmed = None;
+    egui::Window::new("Missing Features")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} enables features that some programs don't define:",
+                job.label()
+            ));
+            for line in missing {
+                ui.label(format!("• {}", line));
+            }
+            ui.label("Those programs will be built without the missing features.");
+            let plan = BuildPlan::new(job);
+            egui::CollapsingHeader::new("Commands")
+                .id_salt("missing_features_plan")
+                .show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| render_plan(ui, &plan));
+                });
+            ui.horizontal(|ui| {
+                if ui.button("Copy Commands").clicked() {
+                    ui.ctx().copy_text(plan.shell_script());
+                }
+                if ui.button("Build Anyway").clicked() {
+                    confirmed = Some(true);
+                }
+                if ui.button("Cancel").clicked() {
+                    confirmed = Some(false);
+                }
+            });
+        });
+    match confirmed {
+        Some(true) => {
+            if let Some((job, _)) = app.pending_profile_build.take() {
+                app.enqueue_build(job);
+            }
+        }
+        Some(false) => app.pending_profile_build = None,
+        None => {}
+    }
+}
+
+fn render_preset_editor(app: &mut BuildTool, ctx: &egui::Context) {
+    let Some(editor) = &mut app.preset_editor else {
+        return;
+    };
+    let mut finished = None;
+    egui::Window::new("Edit Preset")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Preset Name:");
+                ui.text_edit_singleline(&mut editor.draft.name);
+            });
+            ui.label(format!("{} preset", editor.draft.origin.label()));
+            ui.checkbox(&mut editor.draft.run_on_click, "Build when clicked")
+                .on_hover_text("Otherwise clicking the preset only selects its features");
+            ui.horizontal(|ui| {
+                ui.label("Profile:");
+                egui::ComboBox::from_id_salt("preset_editor_profile")
+                    .selected_text(editor.draft.profile.as_deref().unwrap_or("None"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut editor.draft.profile, None, "None");
+                        for profile in &app.config.profiles {
+                            ui.selectable_value(
+                                &mut editor.draft.profile,
+                                Some(profile.name.clone()),
+                                &profile.name,
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text("Adds the profile's features and arguments to the build");
+            });
+            ui.horizontal(|ui| {
+                ui.label("IDL Directory:");
+                let mut dir = editor.draft.build_dir.clone().unwrap_or_default();
+                if ui
+                    .add(egui::TextEdit::singleline(&mut dir).hint_text("From Options"))
+                    .changed()
+                {
+                    editor.draft.build_dir = Some(dir).filter(|d| !d.is_empty());
+                }
+                if ui.button("Browse...").clicked() {
+                    if let Some(path) = FileDialog::new()
+                        .set_directory(&app.workspace_root)
+                        .set_title("Select TypeScript IDL Output Directory")
+                        .pick_folder()
+                    {
+                        if let Some(path_str) = path.to_str() {
+                            editor.draft.build_dir = Some(path_str.to_string());
+                        }
+                    }
+                }
+            });
+            egui::Grid::new("preset_editor_args")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Anchor Args:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut editor.anchor_args)
+                            .hint_text("e.g. --skip-lint"),
+                    )
+                    .on_hover_text(
+                        "Passed to anchor build, after the profile's arguments. \
+                         Quote arguments that contain spaces.",
+                    );
+                    ui.end_row();
+                    ui.label("Cargo Args:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut editor.cargo_args)
+                            .hint_text("e.g. --locked"),
+                    )
+                    .on_hover_text(
+                        "Passed through to cargo, after the profile's arguments. \
+                         Quote arguments that contain spaces.",
+                    );
+                    ui.end_row();
+                    ui.label("Environment:");
+                    ui.add(
+                        egui::TextEdit::multiline(&mut editor.env)
+                            .desired_rows(2)
+                            .hint_text("KEY=VALUE, one per line"),
+                    );
+                    ui.end_row();
+                });
+            ui.separator();
+            ui.label("Programs are built in this order:");
+
+            let mut entry_to_remove = None;
+            let mut entry_to_move = None;
+            let entry_count = editor.draft.programs.len();
+            egui::ScrollArea::vertical()
+                .id_salt("preset_editor")
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for (i, entry) in editor.draft.programs.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.strong(&entry.name);
+                            if ui
+                                .add_enabled(i > 0, egui::Button::new("⏶").small())
+                                .on_hover_text("Build earlier")
+                                .clicked()
+                            {
+                                entry_to_move = Some((i, i - 1));
+                            }
+                            if ui
+                                .add_enabled(i + 1 < entry_count, egui::Button::new("⏷").small())
+                                .on_hover_text("Build later")
+                                .clicked()
+                            {
+                                entry_to_move = Some((i, i + 1));
+                            }
+                            if ui
+                                .small_button("✖")
+                                .on_hover_text("Remove from preset")
+                                .clicked()
+                            {
+                                entry_to_remove = Some(i);
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.add_space(12.0);
+                            ui.checkbox(&mut entry.no_default_features, "No default features")
+                                .on_hover_text("Build with --no-default-features");
+                        });
+                        let program = app.programs.iter().find(|p| p.name == entry.name);
+                        if program.is_none() {
+                            ui.weak("Not in this workspace");
+                        }
+                        // Offer every feature the program defines, plus any the
+                        // preset names that the program no longer has.
+                        let mut names: Vec<String> = program
+                            .map(|p| p.features.iter().map(|f| f.name.clone()).collect())
+                            .unwrap_or_default();
+                        for feature in &entry.features {
+                            if !names.contains(feature) {
+                                names.push(feature.clone());
+                            }
+                        }
+                        ui.horizontal_wrapped(|ui| {
+                            ui.add_space(12.0);
+                            for name in names {
+                                let mut enabled = entry.features.contains(&name);
+                                if ui.checkbox(&mut enabled, &name).changed() {
+                                    if enabled {
+                                        entry.features.push(name);
+                                    } else {
+                                        entry.features.retain(|f| *f != name);
+                                    }
+                                }
+                            }
+                        });
+                        ui.add_space(4.0);
+                    }
+                });
+            if let Some(i) = entry_to_remove {
+                editor.draft.programs.remove(i);
+            }
+            if let Some((from, to)) = entry_to_move {
+                editor.draft.programs.swap(from, to);
+            }
+
+            let available: Vec<&String> = app
+                .programs
+                .iter()
+                .map(|p| &p.name)
+                .filter(|name| !editor.draft.programs.iter().any(|e| e.name == **name))
+                .collect();
+            if !available.is_empty() {
+                egui::ComboBox::from_id_salt("preset_editor_add")
+                    .selected_text("Add Program…")
+                    .show_ui(ui, |ui| {
+                        for name in available {
+                            if ui.selectable_label(false, name).clicked() {
+                                editor.draft.programs.push(PresetProgram {
+                                    name: name.clone(),
+                                    features: Vec::new(),
+                                    no_default_features: false,
+                                });
+                            }
+                        }
+                    });
+            }
+
+            ui.separator();
+            let edited = editor.finish().and_then(|preset| {
+                let violations = preset_violations(&app.programs, &preset, &app.config);
+                if violations.is_empty() {
+                    Ok(preset)
+                } else {
+                    Err(violations.join("\n"))
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(edited.is_ok(), egui::Button::new("Save"))
+                    .clicked()
+                {
+                    finished = Some(edited.as_ref().ok().cloned());
+                }
+                if ui.button("Cancel").clicked() {
+                    finished = Some(None);
+                }
+                if let Err(e) = &edited {
+                    ui.colored_label(ui.visuals().error_fg_color, e.as_str());
+                }
+            });
+        });
+    // `Some(Some(preset))` saves the edit, `Some(None)` discards it.
+    if let Some(edited) = finished {
+        if let Some(editor) = app.preset_editor.take() {
+            if let (Some(edited), Some(preset)) = (edited, app.presets.get_mut(editor.index)) {
+                *preset = edited;
+                app.save_presets();
+            }
+        }
+    }
+}
+
+fn render_preset_repair(app: &mut BuildTool, ctx: &egui::Context) {
+    let Some((index, repair)) = &mut app.preset_repair else {
+        return;
+    };
+    let Some(preset) = app.presets.get(*index) else {
+        app.preset_repair = None;
+        return;
+    };
+    let mut finished = None;
+    egui::Window::new("Fix Preset")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "Preset '{}' refers to things this workspace doesn't have. \
+                 Choose a replacement for each, or remove it from the preset.",
+                preset.name
+            ));
+            ui.separator();
+            egui::Grid::new("preset_repair_grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    for (program, replacement) in &mut repair.programs {
+                        ui.label(format!("Program '{}'", program));
+                        let candidates = app.programs.iter().map(|p| p.name.clone());
+                        replacement_combo(
+                            ui,
+                            ("program", program.as_str()),
+                            replacement,
+                            candidates,
+                        );
+                        ui.end_row();
+                    }
+                    for (program, feature, replacement) in &mut repair.features {
+                        ui.label(format!("{}: feature '{}'", program, feature));
+                        let candidates = app
+                            .programs
+                            .iter()
+                            .find(|p| p.name == *program)
+                            .map(|p| p.features.iter().map(|f| f.name.clone()).collect())
+                            .unwrap_or_else(Vec::new);
+                        replacement_combo(
+                            ui,
+                            ("feature", &format!("{}/{}", program, feature)),
+                            replacement,
+                            candidates,
+                        );
+                        ui.end_row();
+                    }
+                });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Apply").clicked() {
+                    finished = Some(true);
+                }
+                if ui.button("Cancel").clicked() {
+                    finished = Some(false);
+                }
+            });
+        });
+    match finished {
+        Some(true) => {
+            if let Some((index, repair)) = app.preset_repair.take() {
+                if let Some(preset) = app.presets.get_mut(index) {
+                    repair.apply(preset);
+                    app.save_presets();
+                }
+            }
+        }
+        Some(false) => app.preset_repair = None,
+        None => {}
+    }
+}
+
+/// A combo box choosing what replaces a missing name; `None` removes it.
+fn replacement_combo(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    replacement: &mut Option<String>,
+    candidates: impl IntoIterator<Item = String>,
+) {
+    let selected = replacement.clone().unwrap_or_else(|| "Remove".to_string());
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(replacement, None, "Remove");
+            for candidate in candidates {
+                let label = candidate.clone();
+                ui.selectable_value(replacement, Some(candidate), label);
+            }
+        });
+}
+
+/// Lists each step of `plan` with its features and the exact command line.
+fn render_plan(ui: &mut egui::Ui, plan: &BuildPlan) {
+    for step in &plan.steps {
+        let program = &step.program;
+        let mut line = format!("{}: {}", program.name, step.features.join(", "));
+        let defaults = program.default_features();
+        if program.no_default_features {
+            line.push_str(" (--no-default-features)");
+        } else if !defaults.is_empty() {
+            line.push_str(&format!(" (+ default: {})", defaults.join(", ")));
+        }
+        ui.label(line);
+        ui.add(egui::Label::new(egui::RichText::new(step.shell_line()).monospace()).wrap());
+    }
+}
+
+fn render_plan_preview(app: &mut BuildTool, ctx: &egui::Context) {
+    let Some(job) = &app.plan_preview else {
+        return;
+    };
+    let plan = BuildPlan::new(job);
+    let mut open = true;
+    let mut closed = false;
+    egui::Window::new(format!("Build Plan: {}", job.label()))
+        .id(egui::Id::new("plan_preview"))
+        .open(&mut open)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    if plan.steps.is_empty() {
+                        ui.label("This build has no programs.");
+                    }
+                    render_plan(ui, &plan);
+                });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!plan.steps.is_empty(), egui::Button::new("Copy Commands"))
+                    .clicked()
+                {
+                    ui.ctx().copy_text(plan.shell_script());
+                }
+                if ui.button("Close").clicked() {
+                    closed = true;
+                }
+            });
+        });
+    if !open || closed {
+        app.plan_preview = None;
+    }
+}
+
+fn render_blocked_build(app: &mut BuildTool, ctx: &egui::Context) {
+    let Some(violations) = &app.blocked_build else {
+        return;
+    };
+    let mut dismissed = false;
+    egui::Window::new("Conflicting Features")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("The build was not started because it enables conflicting features:");
+            for violation in violations {
+                ui.label(format!("• {}", violation));
+            }
+            ui.label("Change the selection, or turn on No default features if a default feature is involved.");
+            if ui.button("OK").clicked() {
+                dismissed = true;
+            }
+        });
+    if dismissed {
+        app.blocked_build = None;
+    }
+}
+
+fn render_scan_error(app: &mut BuildTool, ui: &mut egui::Ui) {
+    let Some(error) = &app.scan_error else {
+        return;
+    };
+    let mut retry = false;
+    let mut open = None;
+    ui.group(|ui| {
+        ui.colored_label(
+            ui.visuals().error_fg_color,
+            format!(
+                "Could not load programs from {}: {}",
+                app.workspace_root.display(),
+                error
+            ),
+        );
+        if let ScanError::Cargo { stderr } = error {
+            egui::ScrollArea::vertical()
+                .id_salt("scan_error")
+                .max_height(120.0)
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new(stderr.trim()).monospace());
+                });
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Retry").clicked() {
+                retry = true;
+            }
+            if ui.button("Choose Workspace…").clicked() {
+                if let Some(path) = FileDialog::new()
+                    .set_directory(&app.workspace_root)
+                    .set_title("Select Workspace Directory")
+                    .pick_folder()
+                {
+                    open = Some(path);
+                }
+            }
+        });
+    });
+    if let Some(path) = open {
+        app.open_workspace(path);
+    } else if retry {
+        // The workspace root may differ from the one the failed scan fell
+        // back to, so presets and config have to be reloaded with it.
+        app.open_workspace(app.workspace_root.clone());
+    }
+}
+
+fn render_build_queue(app: &mut BuildTool, ui: &mut egui::Ui) {
+    ui.group(|ui| {
+        if let Some(running) = &app.build_state.running {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!("Running: {}", running.label));
+            });
+        }
+        let mut job_to_remove = None;
+        for (i, job) in app.build_state.queue.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}. {}", i + 1, job.label()));
+                if ui
+                    .small_button("✖")
+                    .on_hover_text("Remove from queue")
+                    .clicked()
+                {
+                    job_to_remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = job_to_remove {
+            app.build_state.queue.remove(i);
+        }
+        if app.build_state.queue.len() > 1 && ui.small_button("Clear Queue").clicked() {
+            app.build_state.queue.clear();
+        }
+    });
+}
+
+fn status_badge(ui: &mut egui::Ui, status: &ProgramBuildStatus) {
+    let (icon, text, color) = match status.status {
+        BuildStatus::Running => ("⏳", "running", ui.visuals().warn_fg_color),
+        BuildStatus::Succeeded => ("✔", "succeeded", egui::Color32::from_rgb(80, 180, 80)),
+        BuildStatus::Failed => ("✖", "failed", ui.visuals().error_fg_color),
+        BuildStatus::Cancelled => ("⏹", "cancelled", ui.visuals().weak_text_color()),
+        BuildStatus::Skipped => ("⊘", "skipped", ui.visuals().weak_text_color()),
+    };
+
+    let mut details = match status.status {
+        BuildStatus::Skipped => String::new(),
+        _ => format!("{:.1}s", status.elapsed().as_secs_f32()),
+    };
+    if let Some(finished_at) = status.finished_at {
+        if !details.is_empty() {
+            details.push_str(" · ");
+        }
+        details.push_str(&finished_at.format("%H:%M:%S").to_string());
+    }
+
+    ui.label(egui::RichText::new(icon).color(color))
+        .on_hover_text(format!("Last build {}", text));
+    if !details.is_empty() {
+        ui.label(egui::RichText::new(details).small().weak());
+    }
+}